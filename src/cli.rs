@@ -0,0 +1,139 @@
+use clap::{Args, Parser, Subcommand};
+
+pub const DEFAULT_CONFIG_PATH: &str = "config/config.json";
+
+#[derive(Debug, Parser)]
+#[command(version, about = "NAHO / CWB data receiver")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // used when no subcommand is given, same as `run`
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the receiver (default)
+    Run(RunArgs),
+    /// Load and validate the configuration file
+    CheckConfig(ConfigArgs),
+    /// Feed a rawdata file into the recorders
+    Replay(ReplayArgs),
+    /// Rebuild sqlite3 databases from rawdata files
+    RebuildSqlite(RebuildSqliteArgs),
+    /// Export a sqlite3 table as csv
+    Export(ExportArgs),
+    /// Apply level-1 qc to sqlite3 data and write l1_data
+    Qc(QcArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Path of config.json
+    #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ComponentArgs {
+    /// Disable serial port reader
+    #[arg(long)]
+    pub no_serial: bool,
+    /// Disable network readers
+    #[arg(long)]
+    pub no_network: bool,
+    /// Disable loggernet file listener
+    #[arg(long)]
+    pub no_loggernet: bool,
+    /// Disable rawdata recorder
+    #[arg(long)]
+    pub no_rawdata: bool,
+    /// Disable sqlite3 recorder
+    #[arg(long)]
+    pub no_sqlite3: bool,
+    /// Disable database uploader
+    #[arg(long)]
+    pub no_uploader: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub components: ComponentArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Rawdata file to replay
+    pub file: String,
+    /// Data tag, must exist in `codec`
+    #[arg(long, default_value = "CWB")]
+    pub tag: String,
+    /// Data kind, detected from each line if omitted
+    #[arg(long)]
+    pub dkind: Option<String>,
+    /// Number of header lines at the top of the file
+    #[arg(long)]
+    pub header: Option<usize>,
+    /// Disable rawdata recorder
+    #[arg(long)]
+    pub no_rawdata: bool,
+    /// Disable sqlite3 recorder
+    #[arg(long)]
+    pub no_sqlite3: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RebuildSqliteArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Only rebuild this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Data tag, must exist in `codec`
+    #[arg(long)]
+    pub tag: String,
+    /// Table name (`metadatas.name`)
+    #[arg(long)]
+    pub name: String,
+    /// First date, format: %Y-%m-%d
+    #[arg(long)]
+    pub from: String,
+    /// Last date, format: %Y-%m-%d [default: --from]
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Output csv file [default: stdout]
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct QcArgs {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Only process this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+    /// Only process this table (`metadatas.name`)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// First date, format: %Y-%m-%d
+    #[arg(long)]
+    pub from: String,
+    /// Last date, format: %Y-%m-%d [default: --from]
+    #[arg(long)]
+    pub to: Option<String>,
+}
@@ -0,0 +1,713 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{create_dir_all, rename, File},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::exit,
+    sync::{mpsc, Arc},
+};
+
+use chrono::{Local, NaiveDate};
+
+use crate::{
+    cli::{ComponentArgs, ConfigArgs, ExportArgs, QcArgs, RebuildSqliteArgs, ReplayArgs, RunArgs},
+    component::{
+        codec::CodecConfigBase,
+        dispatcher::{Dispatcher, SinkRegistry},
+        parser_cwb::get_dkind,
+        qc_level1::{filter::Filter, process_level1, sqlite_dedup_and_sort_by, sqlite_get_columns},
+        reader_loggernet::setup_file_listen,
+        reader_network::setup_network_cwb,
+        reader_serial_port::setup_serial_port,
+        supervisor::Supervisor,
+        uploader::setup_uploader,
+        utils::{
+            files::{collect_files, csv_escape, generate_db_filepath},
+            shutdown::Shutdown,
+        },
+        MsgPayload, DTAETIME_FMT,
+    },
+    config::{SinkConfig, SystemConfig},
+};
+
+const DATE_FMT: &str = "%Y-%m-%d";
+const REPLAY_BATCH_SIZE: usize = 100;
+
+fn load_config(args: &ConfigArgs) -> Arc<SystemConfig> {
+    match SystemConfig::load(&args.config) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Load config failed: {e} - {:?}", args.config);
+            exit(exitcode::CONFIG);
+        }
+    }
+}
+
+fn init_logger(config: &SystemConfig) {
+    if let Err(e) = log4rs::init_file(&config.global.log4rs_cfg, Default::default()) {
+        eprintln!("Init logger failed: {e} - {:?}", config.global.log4rs_cfg);
+        exit(exitcode::CONFIG);
+    }
+    log::info!(target: "configuation", "{config:?}");
+}
+
+fn setup_dispatcher<F>(config: Arc<SystemConfig>, enabled: F) -> Dispatcher
+where
+    F: Fn(&SinkConfig) -> bool,
+{
+    match Dispatcher::from_config(config, &SinkRegistry::new(), enabled) {
+        Ok(dispatcher) => dispatcher,
+        Err(e) => {
+            log::error!("Setup dispatcher failed: {e}");
+            exit(exitcode::UNAVAILABLE);
+        }
+    }
+}
+
+// `--no-rawdata` and `--no-sqlite3` disable the builtin sinks by kind
+fn is_sink_enabled(cfg: &SinkConfig, no_rawdata: bool, no_sqlite3: bool) -> bool {
+    match cfg.kind.as_str() {
+        "rawdata" => !no_rawdata,
+        "sqlite3" => !no_sqlite3,
+        _ => true,
+    }
+}
+
+// first signal stops readers and flushes sinks, second one exits immediately
+fn setup_signal_handler() -> Shutdown {
+    let shutdown = Shutdown::new();
+    let flag = shutdown.clone();
+    let ret = ctrlc::set_handler(move || {
+        if flag.trigger() {
+            log::warn!("Shutdown is forced.");
+            exit(exitcode::SOFTWARE);
+        }
+        log::info!("Shutdown signal received.");
+        log::info!(target: "info", "Shutdown signal received, waiting for pending messages.");
+    });
+    if let Err(e) = ret {
+        log::error!("Setup signal handler failed: {e}");
+        exit(exitcode::OSERR);
+    }
+    shutdown
+}
+
+pub fn run(args: &RunArgs) {
+    let config = load_config(&args.config);
+    init_logger(&config);
+
+    let ComponentArgs {
+        no_serial,
+        no_network,
+        no_loggernet,
+        no_rawdata,
+        no_sqlite3,
+        no_uploader,
+    } = args.components;
+
+    let shutdown = setup_signal_handler();
+
+    // readers are restarted by the supervisor until shutdown
+    let mut readers = Supervisor::new(shutdown.clone());
+
+    // bounded, a blocked dispatcher holds the readers instead of growing in memory
+    let (uart_tx, uart_rx) = mpsc::sync_channel(config.global.get_channel_capacity());
+
+    if no_serial {
+        log::info!(target: "info", "Serial port reader is disabled.");
+    } else {
+        for cfg in config.global.get_serial_list() {
+            let name = cfg.get_name().to_string();
+            let cfg = cfg.clone();
+            let tx = uart_tx.clone();
+            let flag = shutdown.clone();
+            let spawn = move || setup_serial_port(&cfg, tx.clone(), flag.clone());
+            if readers.spawn(&name, Box::new(spawn)).is_ok() {
+                log::info!("Setup serial port success: {name}");
+                log::info!(target: "info", "Setup serial port success: {name}");
+            } else {
+                // retried by the supervisor, one missing device doesn't stop the others
+                log::error!("Setup serial port failed: {name}, retry later");
+            };
+        }
+    }
+
+    if no_network {
+        log::info!(target: "info", "Network reader is disabled.");
+    } else {
+        for cfg in config.global.network_list.iter().flatten() {
+            let name = cfg.name.clone();
+            let cfg = cfg.clone();
+            let tx = uart_tx.clone();
+            let flag = shutdown.clone();
+            let spawn = move || setup_network_cwb(&cfg, tx.clone(), flag.clone());
+            if readers.spawn(&name, Box::new(spawn)).is_ok() {
+                log::info!("Setup network reader success: {name}");
+                log::info!(target: "info", "Setup network reader success: {name}");
+            } else {
+                // retried by the supervisor, one missing device doesn't stop the others
+                log::error!("Setup network reader failed: {name}, retry later");
+            };
+        }
+    }
+
+    let no_listen = config
+        .global
+        .listen_list
+        .as_ref()
+        .is_none_or(|val| val.is_empty());
+    if no_loggernet || no_listen {
+        log::info!(target: "info", "Loggernet listener is disabled.");
+    } else {
+        let config = config.clone();
+        let tx = uart_tx.clone();
+        let flag = shutdown.clone();
+        let spawn = move || setup_file_listen(config.clone(), tx.clone(), flag.clone());
+        if readers.spawn("loggernet", Box::new(spawn)).is_ok() {
+            log::info!("Setup loggernet listener success.");
+            log::info!(target: "info", "Setup loggernet listener success.");
+        } else {
+            log::error!("Setup loggernet listener failed.");
+        }
+    }
+
+    if no_uploader || config.global.uploader.is_none() {
+        log::info!(target: "info", "Database uploader is disabled.");
+    } else {
+        let config = config.clone();
+        let flag = shutdown.clone();
+        let spawn = move || setup_uploader(config.clone(), flag.clone());
+        if readers.spawn("uploader", Box::new(spawn)).is_ok() {
+            log::info!("Setup database uploader success.");
+            log::info!(target: "info", "Setup database uploader success.");
+        } else {
+            log::error!("Setup database uploader failed.");
+        }
+    }
+
+    let mut dispatcher = setup_dispatcher(config.clone(), |cfg| {
+        is_sink_enabled(cfg, no_rawdata, no_sqlite3)
+    });
+
+    drop(uart_tx); // release last unused tx
+
+    // until all readers are stopped
+    let code = if dispatcher.run(uart_rx, readers) {
+        exitcode::OK
+    } else {
+        exitcode::SOFTWARE
+    };
+    dispatcher.join();
+
+    log::info!(target: "info", "Shutdown complete.");
+    exit(code);
+}
+
+pub fn check_config(args: &ConfigArgs) {
+    let config = match SystemConfig::load(&args.config) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}: load failed. {e}", args.config);
+            exit(exitcode::CONFIG);
+        }
+    };
+
+    let mut problems = config.check();
+    let registry = SinkRegistry::new();
+    for cfg in config.global.get_sink_list() {
+        if !registry.contains(&cfg.kind) {
+            problems.push(format!(
+                "global.sink_list.{}: unsupport kind {:?}",
+                cfg.get_name(),
+                cfg.kind
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        println!("{}: ok", args.config);
+        return;
+    }
+
+    for problem in problems.iter() {
+        println!("{}: {problem}", args.config);
+    }
+    exit(exitcode::CONFIG);
+}
+
+// number of header lines of the listen target that produced this tag/dkind
+fn get_header_number(config: &SystemConfig, tag: &str, dkind: &str) -> usize {
+    let Some(listen_list) = config.global.listen_list.as_ref() else {
+        return 0;
+    };
+    listen_list
+        .iter()
+        .find(|val| val.tag == tag && val.dkind == dkind)
+        .and_then(|val| val.header.as_ref())
+        .map(|header| header.number)
+        .unwrap_or(0)
+}
+
+// send lines of a rawdata file, consecutive lines with same dkind are batched
+fn replay_file<P>(
+    path: P,
+    tag: &str,
+    dkind: Option<&str>,
+    header: Option<usize>,
+    config: &SystemConfig,
+    dispatcher: &mut Dispatcher,
+) -> Result<usize, Box<dyn Error + 'static>>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(&path)?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header_number = match (header, dkind) {
+        (Some(number), _) => number,
+        (None, Some(dkind)) => get_header_number(config, tag, dkind),
+        (None, None) => 0,
+    };
+    if header_number > 0 {
+        let Some(dkind) = dkind else {
+            return Err(String::from("dkind is required for header.").into());
+        };
+
+        let mut header = Vec::new();
+        for _ in 0..header_number {
+            let Some(line) = lines.next() else {
+                break;
+            };
+            header.push(format!("{}\n", line?));
+        }
+
+        let mut msg = MsgPayload::new(tag, dkind, header);
+        msg.set_update_header()?;
+        dispatcher.send(Arc::new(msg));
+    }
+
+    let mut counter = 0;
+    let mut current: Option<String> = None;
+    let mut values = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let line_dkind = match dkind {
+            Some(dkind) => dkind.to_string(),
+            None => match get_dkind(&line) {
+                Some(dkind) => dkind,
+                None => {
+                    log::error!("Invalid: {line:?}");
+                    continue;
+                }
+            },
+        };
+
+        if current.as_deref() != Some(line_dkind.as_str()) || values.len() >= REPLAY_BATCH_SIZE {
+            if let Some(current) = current.as_deref() {
+                let mut msg = MsgPayload::new(tag, current, values);
+                msg.parse_records();
+                dispatcher.send(Arc::new(msg));
+            }
+            values = Vec::new();
+            current = Some(line_dkind);
+        }
+
+        values.push(line);
+        counter += 1;
+    }
+    if let Some(current) = current.as_deref() {
+        let mut msg = MsgPayload::new(tag, current, values);
+        msg.parse_records();
+        dispatcher.send(Arc::new(msg));
+    }
+
+    log::info!(target: "info", "Replayed {counter} lines from {:?}", path.as_ref());
+    Ok(counter)
+}
+
+pub fn replay(args: &ReplayArgs) {
+    let config = load_config(&args.config);
+    init_logger(&config);
+
+    if !config.codec.contains_key(&args.tag) {
+        eprintln!("Unsupport tag: {:?}", args.tag);
+        exit(exitcode::USAGE);
+    }
+
+    let mut dispatcher = setup_dispatcher(config.clone(), |cfg| {
+        is_sink_enabled(cfg, args.no_rawdata, args.no_sqlite3)
+    });
+    let ret = replay_file(
+        &args.file,
+        &args.tag,
+        args.dkind.as_deref(),
+        args.header,
+        &config,
+        &mut dispatcher,
+    );
+    dispatcher.join();
+
+    match ret {
+        Ok(num) => println!("Replayed {num} lines from {:?}", args.file),
+        Err(e) => {
+            eprintln!("Replay failed: {e} - {:?}", args.file);
+            exit(exitcode::IOERR);
+        }
+    }
+}
+
+// dkind forced for a rawdata file, None if it's detected per line
+// data name is the parent directory when rawdata is seperated by metadatas
+fn get_rebuild_dkind<'a>(codec: &'a CodecConfigBase, file: &Path) -> Option<&'a str> {
+    let dconfig = file
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .and_then(|name| codec.get_data_config_by_name(name))
+        .or(match codec.metadatas.as_slice() {
+            [dconfig] => Some(dconfig),
+            _ => None,
+        })?;
+    // lines of a metadata with several dkinds are told apart by `get_dkind`
+    match dconfig.dkind.as_slice() {
+        [dkind] => Some(dkind.as_str()),
+        _ => None,
+    }
+}
+
+pub fn rebuild_sqlite(args: &RebuildSqliteArgs) {
+    let config = load_config(&args.config);
+    init_logger(&config);
+
+    let mut targets = Vec::new();
+    for (key, val) in config.codec.iter() {
+        if args.tag.as_ref().is_some_and(|tag| tag != key) {
+            continue;
+        }
+        let (Some(cfg_rawdata), Some(cfg_sqlite3)) = (val.rawdata.as_ref(), val.sqlite3.as_ref())
+        else {
+            log::info!("Unsupport rebuild sqlite3: {:?}", key);
+            continue;
+        };
+
+        let mut files = Vec::new();
+        let suffix = cfg_rawdata.suffix.as_deref().unwrap_or("dat");
+        if let Err(e) = collect_files(&cfg_rawdata.directory, suffix, &mut files) {
+            eprintln!(
+                "Read rawdata directory failed: {e} - {:?}",
+                cfg_rawdata.directory
+            );
+            exit(exitcode::IOERR);
+        }
+        files.sort();
+
+        // keep the previous databases
+        let directory = Path::new(&cfg_sqlite3.directory);
+        if directory.exists() {
+            let backup = format!(
+                "{}.{}",
+                cfg_sqlite3.directory.trim_end_matches('/'),
+                Local::now().format("%Y%m%d%H%M%S")
+            );
+            if let Err(e) = rename(directory, &backup) {
+                eprintln!("Backup sqlite3 directory failed: {e} - {directory:?}");
+                exit(exitcode::IOERR);
+            }
+            println!("Backup {directory:?} to {backup:?}");
+        }
+        targets.push((val, files));
+    }
+
+    if targets.is_empty() {
+        eprintln!("Nothing to rebuild.");
+        exit(exitcode::USAGE);
+    }
+
+    let mut dispatcher = setup_dispatcher(config.clone(), |cfg| cfg.kind == "sqlite3");
+    for (codec, files) in targets {
+        for file in files {
+            if let Err(e) = replay_file(
+                &file,
+                &codec.tag,
+                get_rebuild_dkind(codec, &file),
+                None,
+                &config,
+                &mut dispatcher,
+            ) {
+                log::error!("Replay failed: {e} - {file:?}");
+            }
+        }
+    }
+    dispatcher.join();
+}
+
+fn export_table(
+    args: &ExportArgs,
+    config: &SystemConfig,
+) -> Result<usize, Box<dyn Error + 'static>> {
+    let Some(codec) = config.codec.get(&args.tag) else {
+        return Err(format!("Unsupport tag: {:?}", args.tag).into());
+    };
+    let Some(cfg_sqlite3) = codec.sqlite3.as_ref() else {
+        return Err(format!("Unsupport sqlite3: {:?}", args.tag).into());
+    };
+    let Some(dconfig) = codec.get_data_config_by_name(&args.name) else {
+        return Err(format!("Unsupport name: {:?}", args.name).into());
+    };
+    let Some((_, datetime_info)) = dconfig.get_datetime_info() else {
+        return Err(format!("major_datetime not found: {:?}", args.name).into());
+    };
+
+    let from = NaiveDate::parse_from_str(&args.from, DATE_FMT)?;
+    let to = match args.to.as_deref() {
+        Some(to) => NaiveDate::parse_from_str(to, DATE_FMT)?,
+        None => from,
+    };
+
+    let mut output: Box<dyn Write> = match args.output.as_deref() {
+        Some(path) => {
+            if let Some(root) = Path::new(path).parent() {
+                create_dir_all(root)?;
+            }
+            Box::new(File::create(path)?)
+        }
+        None => Box::new(io::stdout()),
+    };
+
+    let mut counter = 0;
+    let mut write_header = true;
+    for date in from.iter_days().take_while(|date| date <= &to) {
+        let mut opts = HashMap::new();
+        opts.insert(
+            "datetime".to_string(),
+            date.and_hms_opt(0, 0, 0)
+                .unwrap()
+                .format(DTAETIME_FMT)
+                .to_string(),
+        );
+        let Some(filepath) = generate_db_filepath(&codec.tag, cfg_sqlite3, dconfig, &opts) else {
+            continue;
+        };
+        if !filepath.exists() {
+            log::info!("Database not found: {filepath:?}");
+            continue;
+        }
+
+        if write_header {
+            let columns = sqlite_get_columns(&filepath, &dconfig.name)?;
+            writeln!(output, "{}", columns.join(","))?;
+            write_header = false;
+        }
+
+        let mut ret = Ok(());
+        sqlite_dedup_and_sort_by(
+            &filepath,
+            &dconfig.name,
+            &datetime_info.sqlite3.name,
+            &datetime_info.sqlite3.name,
+            |row| {
+                let line = row
+                    .iter()
+                    .map(|(_key, val)| csv_escape(val.unwrap_or_default()))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                ret = writeln!(output, "{line}");
+                counter += 1;
+                ret.is_ok()
+            },
+        )?;
+        ret?;
+    }
+    output.flush()?;
+    Ok(counter)
+}
+
+pub fn export(args: &ExportArgs) {
+    let config = load_config(&args.config);
+
+    match export_table(args, &config) {
+        Ok(num) => eprintln!("Exported {num} rows."),
+        Err(e) => {
+            eprintln!("Export failed: {e}");
+            exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+fn qc_level1(args: &QcArgs, config: &SystemConfig) -> Result<usize, Box<dyn Error + 'static>> {
+    let from = NaiveDate::parse_from_str(&args.from, DATE_FMT)?;
+    let to = match args.to.as_deref() {
+        Some(to) => NaiveDate::parse_from_str(to, DATE_FMT)?,
+        None => from,
+    };
+
+    let mut counter = 0;
+    for (key, codec) in config.codec.iter() {
+        if args.tag.as_ref().is_some_and(|tag| tag != key) {
+            continue;
+        }
+        if codec.sqlite3.is_none() || codec.l1_data.is_none() {
+            if args.tag.is_some() {
+                return Err(format!("Unsupport l1_data: {key:?}").into());
+            }
+            continue;
+        }
+
+        let mut filter = Filter::new();
+        if let Some(path) = codec.filter.as_deref() {
+            filter
+                .load(path)
+                .map_err(|e| format!("Load filter failed: {e} - {path:?}"))?;
+        }
+
+        for dconfig in codec.metadatas.iter() {
+            if args.name.as_ref().is_some_and(|name| name != &dconfig.name) {
+                continue;
+            }
+            for date in from.iter_days().take_while(|date| date <= &to) {
+                match process_level1(codec, dconfig, date, &filter) {
+                    Ok(Some((path, num))) => {
+                        log::info!(target: "info", "QC level 1: {num} rows to {path:?}");
+                        counter += num;
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::error!("QC level 1 failed: {e} - {} {date}", dconfig.name),
+                }
+            }
+        }
+    }
+    Ok(counter)
+}
+
+pub fn qc(args: &QcArgs) {
+    let config = load_config(&args.config);
+    init_logger(&config);
+
+    match qc_level1(args, &config) {
+        Ok(num) => eprintln!("QC {num} rows."),
+        Err(e) => {
+            eprintln!("QC failed: {e}");
+            exit(exitcode::SOFTWARE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::component::{
+        dispatcher::Sink,
+        queue::{OverflowPolicy, SinkQueue, DEFAULT_CAPACITY},
+    };
+
+    // (dkind, lines, update_header) of each message
+    type Received = Arc<Mutex<Vec<(String, usize, bool)>>>;
+
+    struct RecordSink {
+        received: Received,
+    }
+
+    impl Sink for RecordSink {
+        fn handle(&mut self, msg: Arc<MsgPayload>) {
+            let item = (msg.dkind.clone(), msg.value.len(), msg.update_header);
+            self.received.lock().unwrap().push(item);
+        }
+    }
+
+    fn record_dispatcher(received: &Received) -> Dispatcher {
+        let mut dispatcher = Dispatcher::new();
+        let queue = SinkQueue::new("record", DEFAULT_CAPACITY, OverflowPolicy::Block, "").unwrap();
+        let received = received.clone();
+        let build = move || {
+            Ok(Box::new(RecordSink {
+                received: received.clone(),
+            }) as Box<dyn Sink>)
+        };
+        dispatcher.register("record", queue, build).unwrap();
+        dispatcher
+    }
+
+    fn system_config() -> SystemConfig {
+        serde_json::from_value(serde_json::json!({
+            "global": {"log4rs_cfg": "config/log4rs.yml"},
+            "codec": {}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn replay_detect_dkind() {
+        let root = std::env::temp_dir().join("naho_replay_test");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        let path = root.join("cwb.dat");
+        std::fs::write(
+            &path,
+            "466920,2025-01-09 15:50:00,MN,1\n\
+             466920,2025-01-09 15:51:00,MN,2\n\
+             466920,2025-01-09 16:00:00,HR,3\n\
+             invalid\n\
+             466920,2025-01-09 15:52:00,MN,4\n",
+        )
+        .unwrap();
+
+        let received = Received::default();
+        let mut dispatcher = record_dispatcher(&received);
+        let num = replay_file(&path, "CWB", None, None, &system_config(), &mut dispatcher);
+        assert_eq!(num.unwrap(), 4);
+        dispatcher.join();
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                ("MN".to_string(), 2, false),
+                ("HR".to_string(), 1, false),
+                ("MN".to_string(), 1, false)
+            ]
+        );
+
+        // header lines are sent first with the forced dkind
+        std::fs::write(&path, "header\n1,a\n2,b\n").unwrap();
+        let received = Received::default();
+        let mut dispatcher = record_dispatcher(&received);
+        let num = replay_file(
+            &path,
+            "NAHO",
+            Some("Min"),
+            Some(1),
+            &system_config(),
+            &mut dispatcher,
+        );
+        assert_eq!(num.unwrap(), 2);
+        dispatcher.join();
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![("Min".to_string(), 1, true), ("Min".to_string(), 2, false)]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rebuild_dkind() {
+        let codec: CodecConfigBase = serde_json::from_value(serde_json::json!({
+            "tag": "CWB",
+            "metadatas": [
+                {"name": "min", "dkind": ["MN"], "formation": []},
+                {"name": "hour", "dkind": ["HR", "HH"], "formation": []}
+            ]
+        }))
+        .unwrap();
+        let dkind = |path: &str| get_rebuild_dkind(&codec, Path::new(path));
+        assert_eq!(dkind("data/raw/min/20250109.dat"), Some("MN"));
+        // several dkinds are detected per line
+        assert_eq!(dkind("data/raw/hour/20250109.dat"), None);
+        assert_eq!(dkind("data/raw/20250109.dat"), None);
+    }
+}
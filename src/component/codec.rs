@@ -1,6 +1,27 @@
+use std::{borrow::Cow, collections::HashMap};
+
 use serde::Deserialize;
 
-use super::INTEGER;
+use super::{
+    parser_sitest::{SensorHealth, SENSORS},
+    INTEGER,
+};
+
+// empty, all `/` (e.g. `/////`) or one of `sentinels`, numbers are compared by value
+pub fn is_missing_value(val: &str, sentinels: &[String]) -> bool {
+    let val = val.trim();
+    if val.is_empty() || val.chars().all(|c| c == '/') {
+        return true;
+    }
+    sentinels.iter().any(|sentinel| {
+        let sentinel = sentinel.trim();
+        sentinel == val
+            || matches!(
+                (sentinel.parse::<f64>(), val.parse::<f64>()),
+                (Ok(lhs), Ok(rhs)) if lhs == rhs
+            )
+    })
+}
 
 #[derive(Debug, Deserialize)]
 pub struct CodecConfig<InnerType> {
@@ -25,6 +46,14 @@ pub struct CodecConfigDataTypeSpec {
     pub dtype: String,
     pub unit: Option<String>,
     pub float_number: Option<INTEGER>,
+    pub missing: Option<Vec<String>>, // missing value sentinels, e.g. ["-9999"]
+}
+
+#[allow(unused)]
+impl CodecConfigDataTypeSpec {
+    pub fn is_missing(&self, val: &str) -> bool {
+        is_missing_value(val, self.missing.as_deref().unwrap_or_default())
+    }
 }
 
 #[allow(unused)]
@@ -44,12 +73,62 @@ pub struct CodecConfigDataTypeSqlite3 {
     pub unit: Option<String>,
 }
 
+// automated level-1 checks, see `qc_level1::checks`
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct CodecConfigDataTypeQc {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,          // max change between consecutive rows
+    pub step_gap: Option<u64>,      // s, rows within this gap are consecutive, default: 60
+    pub persistence: Option<usize>, // flat-line if this many consecutive rows are the same
+    pub upper: Option<String>,      // sqlite3 name of the column this value must not exceed
+}
+
+#[allow(unused)]
+impl CodecConfigDataTypeQc {
+    pub fn get_step_gap(&self) -> u64 {
+        self.step_gap.unwrap_or(60)
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Deserialize, Clone)]
 pub struct CodecConfigDataType {
     pub spec: CodecConfigDataTypeSpec,
     pub rust: CodecConfigDataTypeRust,
     pub sqlite3: CodecConfigDataTypeSqlite3,
+    pub qc: Option<CodecConfigDataTypeQc>,
+}
+
+// firmware variant of `formation`, fields in `omit` are not sent and stored as missing
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CodecConfigLayout {
+    pub name: String,
+    pub omit: Vec<String>, // rust names
+}
+
+// station status code decoded into sensor health flags, see `parser_sitest`
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CodecConfigSitest {
+    pub field: Option<String>,        // rust name, default: dd_sitest
+    pub sensors: Option<Vec<String>>, // sensor of each character, default: SENSORS
+}
+
+#[allow(unused)]
+impl CodecConfigSitest {
+    pub fn get_field(&self) -> &str {
+        self.field.as_deref().unwrap_or("dd_sitest")
+    }
+
+    pub fn get_sensors(&self) -> Vec<String> {
+        match self.sensors.as_ref() {
+            Some(val) => val.clone(),
+            None => SENSORS.iter().map(|val| val.to_string()).collect(),
+        }
+    }
 }
 
 #[allow(unused)]
@@ -60,6 +139,9 @@ pub struct CodecConfigMetadata {
     pub raw_save: Option<bool>,
     pub stx_etx: Option<bool>,
     pub formation: Vec<CodecConfigDataType>,
+    pub layouts: Option<Vec<CodecConfigLayout>>,
+    pub layout: Option<String>, // use this layout only, selected by field count otherwise
+    pub sitest: Option<CodecConfigSitest>,
 }
 
 #[allow(unused)]
@@ -79,12 +161,76 @@ pub struct CodecConfigBase {
     pub tag: String,
     pub rawdata: Option<CodecConfigDB>,
     pub l1_data: Option<CodecConfigDB>,
+    pub filter: Option<String>, // flag table of level-1 qc, see `qc_level1::filter`
     pub sqlite3: Option<CodecConfigDB>,
+    pub rawdata_header: Option<bool>, // write the header of the listen file into rawdata files
     pub metadatas: Vec<CodecConfigMetadata>,
 }
 
 #[allow(unused)]
 impl CodecConfigMetadata {
+    // None if `sitest` is not configured
+    pub fn decode_sitest(&self, code: &str) -> Option<SensorHealth> {
+        let cfg = self.sitest.as_ref()?;
+        Some(SensorHealth::decode(code, &cfg.get_sensors()))
+    }
+
+    // `DEFAULT_LAYOUT` is the full `formation`
+    pub const DEFAULT_LAYOUT: &'static str = "default";
+
+    // (layout name, rawdata in the full `formation`), None if no layout matches
+    pub fn expand_layout<'a>(&self, rawdata: &'a str) -> Option<(&str, Cow<'a, str>)> {
+        let layouts = self.layouts.as_deref().unwrap_or_default();
+        if layouts.is_empty() && self.layout.is_none() {
+            return Some((Self::DEFAULT_LAYOUT, Cow::Borrowed(rawdata)));
+        }
+
+        let mut words = rawdata.split(',').collect::<Vec<_>>();
+        let (stx, etx) = if self.stx_etx == Some(true) && words.len() >= 2 {
+            (words.remove(0), words.pop())
+        } else {
+            ("", None)
+        };
+
+        let layout = match self.layout.as_deref() {
+            Some(Self::DEFAULT_LAYOUT) => None,
+            Some(name) => Some(layouts.iter().find(|val| val.name == name)?),
+            None if words.len() == self.formation.len() => None,
+            None => Some(layouts.iter().find(|val| {
+                self.formation.len().checked_sub(val.omit.len()) == Some(words.len())
+            })?),
+        };
+        let Some(layout) = layout else {
+            return Some((Self::DEFAULT_LAYOUT, Cow::Borrowed(rawdata)));
+        };
+
+        let mut words = words.into_iter();
+        let mut result = Vec::new();
+        if self.stx_etx == Some(true) {
+            result.push(stx);
+        }
+        for dtype in self.formation.iter() {
+            if layout.omit.contains(&dtype.rust.name) {
+                result.push("");
+            } else {
+                result.push(words.next()?);
+            }
+        }
+        if words.next().is_some() {
+            return None;
+        }
+        result.extend(etx);
+        Some((&layout.name, Cow::Owned(result.join(","))))
+    }
+
+    // rust name -> missing value sentinels
+    pub fn get_missing_values(&self) -> HashMap<&str, &[String]> {
+        self.formation
+            .iter()
+            .filter_map(|val| Some((val.rust.name.as_str(), val.spec.missing.as_deref()?)))
+            .collect()
+    }
+
     pub fn get_datetime_info(&self) -> Option<(usize, CodecConfigDataType)> {
         for (idx, val) in self.formation.iter().enumerate() {
             if val.rust.major_datetime == Some(true) {
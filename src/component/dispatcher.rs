@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::config::{SinkConfig, SystemConfig};
+
+use super::{
+    parser_sitest::SensorHealth,
+    queue::SinkQueue,
+    receiver_mqtt::MqttPublisher,
+    receiver_raw::RawdataRecorder,
+    receiver_sqlite::Sqlite3Recorder,
+    supervisor::{Supervised, Supervisor},
+    utils::shutdown::Shutdown,
+    MsgPayload,
+};
+
+const SUPERVISE_INTERVAL: Duration = Duration::from_millis(500);
+const REPORT_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Receiver of the dispatched messages, each sink runs in its own thread
+pub trait Sink: Send {
+    fn setup(&mut self) -> Result<(), Box<dyn Error + 'static>> {
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: Arc<MsgPayload>);
+
+    // called after all pending messages are handled
+    fn shutdown(&mut self) {}
+}
+
+pub type SinkFactory =
+    fn(&SinkConfig, Arc<SystemConfig>) -> Result<Box<dyn Sink>, Box<dyn Error + 'static>>;
+
+// build sinks from `global.sink_list` by kind
+pub struct SinkRegistry {
+    factories: HashMap<String, SinkFactory>,
+}
+
+impl SinkRegistry {
+    pub fn new() -> Self {
+        let mut registry = SinkRegistry {
+            factories: HashMap::new(),
+        };
+        registry.register("rawdata", |_cfg, config| {
+            Ok(Box::new(RawdataRecorder::new(config)))
+        });
+        registry.register("sqlite3", |_cfg, config| {
+            Ok(Box::new(Sqlite3Recorder::new(config)))
+        });
+        registry.register("mqtt", |cfg, config| {
+            Ok(Box::new(MqttPublisher::new(cfg, config)?))
+        });
+        registry
+    }
+
+    pub fn register(&mut self, kind: &str, factory: SinkFactory) {
+        self.factories.insert(kind.to_string(), factory);
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    pub fn get(&self, kind: &str) -> Option<SinkFactory> {
+        self.factories.get(kind).copied()
+    }
+}
+
+pub fn spawn_sink(
+    name: &str,
+    mut sink: Box<dyn Sink>,
+    queue: Arc<SinkQueue>,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    sink.setup()?;
+
+    let handler = thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let mut counter = 0;
+            while let Some(msg) = queue.pop() {
+                sink.handle(msg);
+                counter += 1;
+            }
+            sink.shutdown();
+            counter
+        })?;
+    Ok(handler)
+}
+
+type BuildFn = Box<dyn Fn() -> Result<Box<dyn Sink>, Box<dyn Error + 'static>> + Send>;
+
+struct SinkEntry {
+    build: BuildFn,
+    // messages received while the sink is down are kept in the queue
+    queue: Arc<SinkQueue>,
+    state: Supervised,
+}
+
+impl SinkEntry {
+    // a stalled sink doesn't hold the other sinks, after `block_timeout` or on shutdown
+    // its messages are spooled until it catches up, nothing is dropped
+    fn send(&mut self, msg: Arc<MsgPayload>, shutdown: &Shutdown) {
+        let name = self.state.name().to_string();
+        let deadline = Instant::now() + self.queue.block_timeout();
+        let mut msg = msg;
+        let mut blocked = false;
+        while let Err(ret) = self.queue.push(msg) {
+            if !blocked {
+                log::warn!("Queue of {name} is full, waiting.");
+                blocked = true;
+            }
+            msg = ret;
+            self.supervise();
+            if shutdown.is_triggered() || Instant::now() >= deadline {
+                match self.queue.spill(msg) {
+                    Ok(()) => {
+                        log::error!(
+                            "Sink {name} is stalled, messages are spooled until it catches up."
+                        );
+                        return;
+                    }
+                    // keep waiting
+                    Err(ret) => msg = ret,
+                }
+            }
+        }
+    }
+
+    fn supervise(&mut self) {
+        if !self.state.need_restart() {
+            return;
+        }
+
+        let ret =
+            (self.build)().and_then(|sink| spawn_sink(self.state.name(), sink, self.queue.clone()));
+        self.state.restarted(ret);
+    }
+}
+
+// fan out each message to all registered sinks
+#[derive(Default)]
+pub struct Dispatcher {
+    sinks: Vec<SinkEntry>,
+    config: Option<Arc<SystemConfig>>,
+    layouts: HashMap<(String, String), String>, // (tag, dkind) -> last matched layout
+    health: HashMap<(String, String, String), SensorHealth>, // (tag, dkind, station) -> last health
+    shutdown: Shutdown,                         // blocked sends give up after shutdown
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Dispatcher::default()
+    }
+
+    // sinks in `global.sink_list` accepted by `enabled`
+    pub fn from_config<F>(
+        config: Arc<SystemConfig>,
+        registry: &SinkRegistry,
+        enabled: F,
+    ) -> Result<Self, Box<dyn Error + 'static>>
+    where
+        F: Fn(&SinkConfig) -> bool,
+    {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.config = Some(config.clone());
+        for cfg in config.global.get_sink_list() {
+            let name = cfg.get_name().to_string();
+            if !enabled(&cfg) {
+                log::info!(target: "info", "Sink is disabled: {name}");
+                continue;
+            }
+
+            let Some(factory) = registry.get(&cfg.kind) else {
+                return Err(format!("Unsupport sink kind: {:?}", cfg.kind).into());
+            };
+            let queue = SinkQueue::from_config(&cfg)?;
+            let config = config.clone();
+            let build = move || factory(&cfg, config.clone());
+            if let Err(e) = dispatcher.register(&name, queue, build) {
+                return Err(format!("Setup sink {name:?} failed: {e}").into());
+            }
+            log::info!("Setup sink success: {name}");
+            log::info!(target: "info", "Setup sink success: {name}");
+        }
+
+        if dispatcher.sinks.is_empty() {
+            log::warn!("All sinks are disabled.");
+        }
+        Ok(dispatcher)
+    }
+
+    // `build` is called again to restart the sink after it panicked
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        queue: SinkQueue,
+        build: F,
+    ) -> Result<(), Box<dyn Error + 'static>>
+    where
+        F: Fn() -> Result<Box<dyn Sink>, Box<dyn Error + 'static>> + Send + 'static,
+    {
+        let queue = Arc::new(queue);
+        let handler = spawn_sink(name, build()?, queue.clone())?;
+        self.sinks.push(SinkEntry {
+            build: Box::new(build),
+            queue,
+            state: Supervised::new(name, handler),
+        });
+        Ok(())
+    }
+
+    pub fn send(&mut self, msg: Arc<MsgPayload>) {
+        let msg = self.apply_codec(msg);
+        self.check_health(&msg);
+        for sink in self.sinks.iter_mut() {
+            sink.send(msg.clone(), &self.shutdown);
+        }
+    }
+
+    // firmware layouts and missing value sentinels of the codec are applied to the typed records
+    fn apply_codec(&mut self, msg: Arc<MsgPayload>) -> Arc<MsgPayload> {
+        let Some(config) = self.config.clone() else {
+            return msg;
+        };
+        let Some(dconfig) = config
+            .codec
+            .get(&msg.tag)
+            .and_then(|val| val.get_data_config(&msg.dkind))
+        else {
+            return msg;
+        };
+        if msg.update_header {
+            return msg;
+        }
+
+        let sentinels = dconfig.get_missing_values();
+        if dconfig.layouts.is_none() && dconfig.layout.is_none() && sentinels.is_empty() {
+            msg.log_records();
+            return msg;
+        }
+
+        let mut msg = Arc::unwrap_or_clone(msg);
+        let layouts = msg.apply_layouts(dconfig);
+        for (value, layout) in msg.value.iter().zip(layouts) {
+            let Some(layout) = layout else {
+                log::warn!("Layout not found: {value:?}");
+                continue;
+            };
+            let key = (msg.tag.clone(), msg.dkind.clone());
+            if self.layouts.get(&key).map(|val| val.as_str()) != Some(layout) {
+                log::info!(target: "info", "Layout matched: {} {} {layout}", msg.tag, msg.dkind);
+                self.layouts.insert(key, layout.to_string());
+            }
+        }
+        msg.apply_missing(&sentinels);
+        msg.log_records();
+        Arc::new(msg)
+    }
+
+    // sensors turned to fault are warned on target "health"
+    fn check_health(&mut self, msg: &MsgPayload) {
+        let Some(config) = self.config.clone() else {
+            return;
+        };
+        let Some(dconfig) = config
+            .codec
+            .get(&msg.tag)
+            .and_then(|val| val.get_data_config(&msg.dkind))
+        else {
+            return;
+        };
+        let Some(cfg) = dconfig.sitest.as_ref() else {
+            return;
+        };
+
+        for record in msg.records.iter().flatten() {
+            let fields = record.to_json();
+            let Some(code) = fields.get(cfg.get_field()).and_then(|val| val.as_str()) else {
+                continue;
+            };
+            let Some(health) = dconfig.decode_sitest(code) else {
+                continue;
+            };
+            let station = fields
+                .get("station")
+                .or(fields.get("stationid"))
+                .and_then(|val| val.as_str())
+                .unwrap_or("unknown");
+
+            let key = (msg.tag.clone(), msg.dkind.clone(), station.to_string());
+            let prev = self.health.get(&key);
+            for sensor in health.new_faults(prev) {
+                log::warn!(
+                    target: "health",
+                    "Sensor fault: {} {} {station} {sensor} - {code:?}",
+                    msg.tag,
+                    msg.dkind
+                );
+            }
+            for sensor in health.recovered(prev) {
+                log::info!(
+                    target: "health",
+                    "Sensor recovered: {} {} {station} {sensor} - {code:?}",
+                    msg.tag,
+                    msg.dkind
+                );
+            }
+            self.health.insert(key, health);
+        }
+    }
+
+    pub fn supervise(&mut self) {
+        for sink in self.sinks.iter_mut() {
+            sink.supervise();
+        }
+    }
+
+    // (name, restarts)
+    pub fn restart_counts(&self) -> Vec<(String, usize)> {
+        self.sinks
+            .iter()
+            .map(|val| (val.state.name().to_string(), val.state.restarts()))
+            .collect()
+    }
+
+    // log restart counts if any component has been restarted
+    fn report(&self, readers: &Supervisor) {
+        let counts = readers
+            .restart_counts()
+            .into_iter()
+            .chain(self.restart_counts())
+            .filter(|(_, restarts)| *restarts > 0)
+            .map(|(name, restarts)| format!("{name}: {restarts}"))
+            .collect::<Vec<_>>();
+        if !counts.is_empty() {
+            log::warn!(target: "info", "Restart counts: {}", counts.join(", "));
+        }
+    }
+
+    // dispatch until all readers are stopped, crashed readers and sinks are restarted meanwhile
+    pub fn run(&mut self, receiver: Receiver<Arc<MsgPayload>>, mut readers: Supervisor) -> bool {
+        self.shutdown = readers.shutdown().clone();
+        let mut last_check = Instant::now();
+        let mut last_report = Instant::now();
+        loop {
+            match receiver.recv_timeout(SUPERVISE_INTERVAL) {
+                Ok(msg) => self.send(msg),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if last_check.elapsed() >= SUPERVISE_INTERVAL {
+                readers.supervise();
+                self.supervise();
+                if readers.is_finished() {
+                    break;
+                }
+                last_check = Instant::now();
+            }
+
+            if last_report.elapsed() >= REPORT_INTERVAL {
+                self.report(&readers);
+                last_report = Instant::now();
+            }
+        }
+
+        // release senders held by readers, then drain the channel
+        let result = readers.join();
+        while let Ok(msg) = receiver.recv() {
+            self.send(msg);
+        }
+        result
+    }
+
+    // close queues and wait until all pending messages are handled
+    pub fn join(self) {
+        for sink in self.sinks {
+            let SinkEntry { queue, state, .. } = sink;
+            queue.close();
+
+            let name = state.name().to_string();
+            let restarts = state.restarts();
+            if state.is_finished() && queue.len() > 0 {
+                log::error!("Sink {name} is down, {} messages are dropped.", queue.len());
+            }
+            match state.join() {
+                Ok(num) => log::info!(
+                    target: "info",
+                    "Sink {name} handled {num} messages, restarts: {restarts}"
+                ),
+                Err(e) => log::error!("Sink {name} panicked: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{mpsc, Mutex};
+
+    use crate::component::{
+        queue::{OverflowPolicy, DEFAULT_CAPACITY},
+        utils::shutdown::Shutdown,
+    };
+
+    use super::*;
+
+    struct CountSink {
+        count: Arc<Mutex<usize>>,
+    }
+
+    impl Sink for CountSink {
+        fn handle(&mut self, msg: Arc<MsgPayload>) {
+            if msg.dkind == "PANIC" {
+                panic!("bad message");
+            }
+            *self.count.lock().unwrap() += 1;
+        }
+    }
+
+    fn count_sink(count: &Arc<Mutex<usize>>) -> BuildFn {
+        let count = count.clone();
+        Box::new(move || {
+            Ok(Box::new(CountSink {
+                count: count.clone(),
+            }))
+        })
+    }
+
+    fn block_queue(name: &str) -> SinkQueue {
+        SinkQueue::new(name, DEFAULT_CAPACITY, OverflowPolicy::Block, "").unwrap()
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut dispatcher = Dispatcher::new();
+        let counts = (0..3).map(|_| Arc::new(Mutex::new(0))).collect::<Vec<_>>();
+        for (idx, count) in counts.iter().enumerate() {
+            let name = format!("count{idx}");
+            dispatcher
+                .register(&name, block_queue(&name), count_sink(count))
+                .unwrap();
+        }
+
+        let (tx, rx) = mpsc::sync_channel(16);
+        for _ in 0..5 {
+            tx.send(Arc::new(MsgPayload::new("CWB", "MN", Vec::new())))
+                .unwrap();
+        }
+        drop(tx);
+
+        assert!(dispatcher.run(rx, Supervisor::new(Shutdown::new())));
+        dispatcher.join();
+        for count in counts {
+            assert_eq!(*count.lock().unwrap(), 5);
+        }
+    }
+
+    #[test]
+    fn restart_sink() {
+        let mut dispatcher = Dispatcher::new();
+        let count = Arc::new(Mutex::new(0));
+        dispatcher
+            .register("count", block_queue("count"), count_sink(&count))
+            .unwrap();
+
+        dispatcher.send(Arc::new(MsgPayload::new("CWB", "PANIC", Vec::new())));
+        let mut sent = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while dispatcher.restart_counts()[0].1 == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(100));
+            dispatcher.send(Arc::new(MsgPayload::new("CWB", "MN", Vec::new())));
+            dispatcher.supervise();
+            sent += 1;
+        }
+        assert_eq!(dispatcher.restart_counts(), vec![("count".to_string(), 1)]);
+
+        // messages kept in the queue while the sink is down are handled after restart
+        dispatcher.join();
+        assert_eq!(*count.lock().unwrap(), sent);
+    }
+
+    // waits until `release` is set
+    struct StuckSink {
+        release: Arc<Mutex<bool>>,
+        count: Arc<Mutex<usize>>,
+    }
+
+    impl Sink for StuckSink {
+        fn handle(&mut self, _msg: Arc<MsgPayload>) {
+            while !*self.release.lock().unwrap() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            *self.count.lock().unwrap() += 1;
+        }
+    }
+
+    #[test]
+    fn stalled_sink() {
+        let mut dispatcher = Dispatcher::new();
+        let directory = std::env::temp_dir().join("naho_stalled_test");
+        let directory = directory.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(directory);
+        let release = Arc::new(Mutex::new(false));
+        let handled = Arc::new(Mutex::new(0));
+        let stuck = SinkQueue::new("stuck", 1, OverflowPolicy::Block, directory)
+            .unwrap()
+            .with_block_timeout(Duration::from_millis(200));
+        let build = {
+            let release = release.clone();
+            let handled = handled.clone();
+            move || {
+                Ok(Box::new(StuckSink {
+                    release: release.clone(),
+                    count: handled.clone(),
+                }) as Box<dyn Sink>)
+            }
+        };
+        dispatcher.register("stuck", stuck, build).unwrap();
+        let count = Arc::new(Mutex::new(0));
+        dispatcher
+            .register("count", block_queue("count"), count_sink(&count))
+            .unwrap();
+
+        // only the first overflowed message waits for `block_timeout`
+        let start = Instant::now();
+        for _ in 0..20 {
+            dispatcher.send(Arc::new(MsgPayload::new("CWB", "MN", Vec::new())));
+        }
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // the overflow is spooled, nothing is lost once the sink catches up
+        *release.lock().unwrap() = true;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *handled.lock().unwrap() < 20 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*handled.lock().unwrap(), 20);
+        dispatcher.join();
+        assert_eq!(*count.lock().unwrap(), 20);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{mpsc::SyncSender, Arc},
+};
+
+use serde::Deserialize;
+
+use super::{parser_cwb::get_dkind, MsgPayload};
+
+pub const STX: u8 = 0x2;
+pub const ETX: u8 = 0x3;
+const MAX_FRAME_SIZE: usize = 4096;
+
+// Checksum is calculated over the bytes after STX up to and including ETX,
+// and transmitted as 2 hex ascii characters right after ETX.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumType {
+    None,
+    // frames are validated unless `none` is chosen on purpose
+    #[default]
+    Xor,
+    Sum,
+}
+
+impl ChecksumType {
+    fn length(&self) -> usize {
+        match self {
+            ChecksumType::None => 0,
+            ChecksumType::Xor | ChecksumType::Sum => 2,
+        }
+    }
+
+    pub fn calculate(&self, data: &[u8]) -> u8 {
+        match self {
+            ChecksumType::None => 0,
+            ChecksumType::Xor => data.iter().fold(0, |acc, val| acc ^ val),
+            ChecksumType::Sum => data.iter().fold(0, |acc: u8, val| acc.wrapping_add(*val)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameErrorType {
+    Malformed,
+    Overflow,
+    Checksum,
+}
+
+impl Display for FrameErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameErrorType::Malformed => write!(f, "FrameErrorType::Malformed"),
+            FrameErrorType::Overflow => write!(f, "FrameErrorType::Overflow"),
+            FrameErrorType::Checksum => write!(f, "FrameErrorType::Checksum"),
+        }
+    }
+}
+
+impl Error for FrameErrorType {}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameStats {
+    pub accepted: usize,
+    pub rejected: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FrameState {
+    Idle,
+    Data,
+    Checksum,
+}
+
+// STX ... ETX [checksum] frame decoder, bytes outside a frame are discarded
+#[derive(Debug)]
+pub struct CwbFrameDecoder {
+    checksum: ChecksumType,
+    state: FrameState,
+    frame: Vec<u8>,
+    checksum_buf: Vec<u8>,
+    stats: FrameStats,
+    log_target: &'static str, // frames and decode errors are logged on the reader's target
+}
+
+impl CwbFrameDecoder {
+    pub fn new(checksum: ChecksumType) -> Self {
+        CwbFrameDecoder {
+            checksum,
+            state: FrameState::Idle,
+            frame: Vec::new(),
+            checksum_buf: Vec::new(),
+            stats: FrameStats::default(),
+            log_target: "serialport",
+        }
+    }
+
+    // default: serialport
+    pub fn with_log_target(mut self, target: &'static str) -> Self {
+        self.log_target = target;
+        self
+    }
+
+    pub fn log_target(&self) -> &'static str {
+        self.log_target
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    // drop the partial frame, e.g. after reconnect
+    pub fn reset(&mut self) {
+        self.state = FrameState::Idle;
+        self.frame.clear();
+        self.checksum_buf.clear();
+    }
+
+    // return frames completed by `data`, each frame is decoded from STX to ETX
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Result<String, FrameErrorType>> {
+        let mut result = Vec::new();
+        for &byte in data {
+            if let Some(ret) = self.push(byte) {
+                match ret {
+                    Ok(_) => self.stats.accepted += 1,
+                    Err(_) => self.stats.rejected += 1,
+                }
+                result.push(ret);
+            }
+        }
+        result
+    }
+
+    fn push(&mut self, byte: u8) -> Option<Result<String, FrameErrorType>> {
+        match self.state {
+            FrameState::Idle => {
+                if byte == STX {
+                    self.frame.push(byte);
+                    self.state = FrameState::Data;
+                }
+                None
+            }
+            FrameState::Data => {
+                // new frame starts before previous one ends
+                if byte == STX {
+                    self.reset();
+                    self.frame.push(byte);
+                    self.state = FrameState::Data;
+                    return Some(Err(FrameErrorType::Malformed));
+                }
+
+                if self.frame.len() >= MAX_FRAME_SIZE {
+                    self.reset();
+                    return Some(Err(FrameErrorType::Overflow));
+                }
+
+                self.frame.push(byte);
+                if byte != ETX {
+                    return None;
+                }
+
+                if self.checksum == ChecksumType::None {
+                    return Some(self.finish());
+                }
+                self.state = FrameState::Checksum;
+                None
+            }
+            FrameState::Checksum => {
+                // checksum byte is lost, keep the next frame
+                if byte == STX {
+                    log::warn!(target: self.log_target, "Checksum missing: {:?}", self.frame);
+                    self.reset();
+                    self.frame.push(byte);
+                    self.state = FrameState::Data;
+                    return Some(Err(FrameErrorType::Malformed));
+                }
+                self.checksum_buf.push(byte);
+                if self.checksum_buf.len() < self.checksum.length() {
+                    return None;
+                }
+                Some(self.finish())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<String, FrameErrorType> {
+        let ret = self.verify();
+        let frame = String::from_utf8_lossy(&self.frame).to_string();
+        if let Err(e) = ret {
+            log::warn!(target: self.log_target, "{e}: {frame:?} {:?}", self.checksum_buf);
+        }
+        self.reset();
+        ret.map(|_| frame)
+    }
+
+    fn verify(&self) -> Result<(), FrameErrorType> {
+        if self.checksum == ChecksumType::None {
+            return Ok(());
+        }
+
+        let Ok(text) = std::str::from_utf8(&self.checksum_buf) else {
+            return Err(FrameErrorType::Malformed);
+        };
+        let Ok(expected) = u8::from_str_radix(text, 16) else {
+            return Err(FrameErrorType::Malformed);
+        };
+
+        // skip STX
+        if self.checksum.calculate(&self.frame[1..]) != expected {
+            return Err(FrameErrorType::Checksum);
+        }
+        Ok(())
+    }
+}
+
+// decode `data` and send completed frames as `MsgPayload`, return number of sent frames
+pub fn dispatch_frames(
+    decoder: &mut CwbFrameDecoder,
+    data: &[u8],
+    tag: &str,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> usize {
+    let mut counter = 0;
+    for frame in decoder.feed(data) {
+        let msg = match frame {
+            Ok(msg) => msg,
+            Err(e) => {
+                let stats = decoder.stats();
+                log::warn!(
+                    "Reject frame: {e}. accepted: {}, rejected: {}",
+                    stats.accepted,
+                    stats.rejected
+                );
+                continue;
+            }
+        };
+        log::info!(target: "console", "{:?}", msg);
+        log::info!(target: decoder.log_target(), "{:?}", msg);
+
+        let Some(dkind) = get_dkind(&msg) else {
+            log::error!("Invalid data: {msg:?}");
+            continue;
+        };
+
+        let mut payload = MsgPayload::new(tag, &dkind, vec![msg]);
+        payload.parse_records();
+        match sender.send(Arc::new(payload)) {
+            Ok(_) => counter += 1,
+            Err(e) => log::error!("{e}"),
+        }
+    }
+    counter
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode(data: &str, checksum: ChecksumType) -> Vec<u8> {
+        let mut frame = vec![STX];
+        frame.extend_from_slice(data.as_bytes());
+        frame.push(ETX);
+        if checksum != ChecksumType::None {
+            let value = checksum.calculate(&frame[1..]);
+            frame.extend_from_slice(format!("{value:02X}").as_bytes());
+        }
+        frame
+    }
+
+    #[test]
+    fn decode_without_checksum() {
+        let mut decoder = CwbFrameDecoder::new(ChecksumType::None);
+        let mut data = b"noise".to_vec();
+        data.extend(encode(",466920,MN,202501091555,", ChecksumType::None));
+        data.extend(b"\r\n");
+
+        let ret = decoder.feed(&data);
+        assert_eq!(
+            ret,
+            vec![Ok("\u{2},466920,MN,202501091555,\u{3}".to_string())]
+        );
+        assert_eq!(decoder.stats().accepted, 1);
+    }
+
+    #[test]
+    fn decode_with_checksum() {
+        // frames are validated by default
+        assert_eq!(ChecksumType::default(), ChecksumType::Xor);
+        for checksum in [ChecksumType::Xor, ChecksumType::Sum] {
+            let mut decoder = CwbFrameDecoder::new(checksum);
+            let data = encode(",466920,HR,202501091600,", checksum);
+
+            // split into several reads
+            let (head, tail) = data.split_at(10);
+            assert!(decoder.feed(head).is_empty());
+            let ret = decoder.feed(tail);
+            assert_eq!(ret.len(), 1);
+            assert!(ret[0].is_ok());
+        }
+    }
+
+    #[test]
+    fn reject_corrupt_frame() {
+        let mut decoder = CwbFrameDecoder::new(ChecksumType::Xor);
+        let mut data = encode(",466920,MN,202501091555,", ChecksumType::Xor);
+        data[5] = b'0';
+        data.extend(encode(",466920,MN,202501091556,", ChecksumType::Xor));
+
+        let ret = decoder.feed(&data);
+        assert_eq!(ret[0], Err(FrameErrorType::Checksum));
+        assert!(ret[1].is_ok());
+        assert_eq!(decoder.stats().accepted, 1);
+        assert_eq!(decoder.stats().rejected, 1);
+    }
+
+    #[test]
+    fn reject_unterminated_frame() {
+        let mut decoder = CwbFrameDecoder::new(ChecksumType::None);
+        let mut data = vec![STX];
+        data.extend(b",466920,MN,2025");
+        data.extend(encode(",466920,MN,202501091556,", ChecksumType::None));
+
+        let ret = decoder.feed(&data);
+        assert_eq!(ret[0], Err(FrameErrorType::Malformed));
+        assert!(ret[1].is_ok());
+    }
+
+    #[test]
+    fn lost_checksum_byte() {
+        let mut decoder = CwbFrameDecoder::new(ChecksumType::Xor);
+        let mut data = encode(",466920,MN,202501091555,", ChecksumType::Xor);
+        data.pop();
+        data.extend(encode(",466920,MN,202501091556,", ChecksumType::Xor));
+
+        let ret = decoder.feed(&data);
+        assert_eq!(ret[0], Err(FrameErrorType::Malformed));
+        assert_eq!(ret[1], Ok("\u{2},466920,MN,202501091556,\u{3}".to_string()));
+    }
+}
@@ -1,24 +1,40 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
+
+use codec::CodecConfigMetadata;
+use parser_cwb::{get_dkind, CwbRecord};
+use serde::{Deserialize, Serialize};
 
 pub mod codec;
+pub mod dispatcher;
+pub mod frame_cwb;
 pub mod parser_cwb;
+pub mod parser_sitest;
+pub mod parser_toa5;
 pub mod qc_level1;
+pub mod queue;
 pub mod reader_loggernet;
+pub mod reader_network;
 pub mod reader_serial_port;
+pub mod receiver_mqtt;
 pub mod receiver_raw;
 pub mod receiver_sqlite;
+pub mod supervisor;
+pub mod uploader;
 pub mod utils;
 
 type INTEGER = i64;
 type FLOAT = f64;
 pub const DTAETIME_FMT: &str = "%Y-%m-%d %H:%M:%S";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MsgPayload {
     pub tag: String,
     pub dkind: String,
     pub update_header: bool, // if true, value is header informatino
     pub value: Vec<String>,
+    // parsed `value`, None if it isn't a valid CWB frame
+    #[serde(default)]
+    pub records: Vec<Option<CwbRecord>>,
 }
 
 impl MsgPayload {
@@ -28,6 +44,58 @@ impl MsgPayload {
             dkind: dkind.to_string(),
             update_header: false,
             value,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn parse_records(&mut self) {
+        self.records = self
+            .value
+            .iter()
+            .map(|val| CwbRecord::parse_from_str(val).ok())
+            .collect();
+    }
+
+    pub fn get_record(&self, idx: usize) -> Option<&CwbRecord> {
+        self.records.get(idx).and_then(|val| val.as_ref())
+    }
+
+    // re-parse frames of other firmware layouts, return the matched layout of each value
+    pub fn apply_layouts<'a>(&mut self, config: &'a CodecConfigMetadata) -> Vec<Option<&'a str>> {
+        let mut result = Vec::new();
+        for (idx, value) in self.value.iter().enumerate() {
+            let Some((layout, rawdata)) = config.expand_layout(value) else {
+                result.push(None);
+                continue;
+            };
+            if layout != CodecConfigMetadata::DEFAULT_LAYOUT && idx < self.records.len() {
+                self.records[idx] = CwbRecord::parse_from_str(&rawdata).ok();
+            }
+            result.push(Some(layout));
+        }
+        result
+    }
+
+    // rust name -> missing value sentinels, see `CodecConfigMetadata::get_missing_values`
+    pub fn apply_missing(&mut self, sentinels: &HashMap<&str, &[String]>) {
+        for record in self.records.iter_mut().flatten() {
+            record.apply_missing(sentinels);
+        }
+    }
+
+    // invalid CWB frames and missing values of each record
+    pub fn log_records(&self) {
+        for (value, record) in self.value.iter().zip(self.records.iter()) {
+            let Some(record) = record else {
+                if get_dkind(value).is_some() {
+                    log::warn!("Invalid CWB record: {value:?}");
+                }
+                continue;
+            };
+            let count = record.missing_count();
+            if count > 0 {
+                log::debug!("Missing values: {count} - {value:?}");
+            }
         }
     }
 
@@ -1,13 +1,14 @@
 #![allow(unused)]
 
-use std::{error::Error, fs::File, io::BufReader, str::Split};
+use std::{collections::HashMap, error::Error, fs::File, io::BufReader, str::Split};
 
 use chrono::{NaiveDateTime, NaiveTime};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::{
-    codec::{CodecConfig, CodecConfigBase, CodecConfigMetadata},
-    FLOAT, INTEGER,
+    codec::{is_missing_value, CodecConfig, CodecConfigBase, CodecConfigMetadata},
+    parser_sitest::HEALTH_COLUMNS,
+    DTAETIME_FMT, FLOAT, INTEGER,
 };
 
 pub fn get_dkind(data: &str) -> Option<String> {
@@ -29,6 +30,26 @@ pub fn get_dkind(data: &str) -> Option<String> {
     }
 }
 
+// same format as sqlite3 and rawdata
+mod datetime_format {
+    use super::*;
+
+    pub fn serialize<S>(time: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&time.format(DTAETIME_FMT).to_string())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        NaiveDateTime::parse_from_str(&text, DTAETIME_FMT).map_err(serde::de::Error::custom)
+    }
+}
+
 fn parse_string(spliter: &mut Split<char>) -> Result<String, Box<dyn Error + 'static>> {
     if let Some(val) = spliter.next() {
         log::debug!("{val}");
@@ -38,19 +59,39 @@ fn parse_string(spliter: &mut Split<char>) -> Result<String, Box<dyn Error + 'st
     }
 }
 
-fn parse_integer(spliter: &mut Split<char>) -> Result<INTEGER, Box<dyn Error + 'static>> {
+fn parse_optional_string(
+    spliter: &mut Split<char>,
+) -> Result<Option<String>, Box<dyn Error + 'static>> {
     if let Some(val) = spliter.next() {
         log::debug!("{val}");
-        Ok(val.parse::<INTEGER>()?)
+        if is_missing_value(val, &[]) {
+            return Ok(None);
+        }
+        Ok(Some(val.to_string()))
     } else {
         Err(String::from("System error").into())
     }
 }
 
-fn parse_float(spliter: &mut Split<char>) -> Result<FLOAT, Box<dyn Error + 'static>> {
+fn parse_integer(spliter: &mut Split<char>) -> Result<Option<INTEGER>, Box<dyn Error + 'static>> {
     if let Some(val) = spliter.next() {
         log::debug!("{val}");
-        Ok(val.parse::<FLOAT>()?)
+        if is_missing_value(val, &[]) {
+            return Ok(None);
+        }
+        Ok(Some(val.trim().parse::<INTEGER>()?))
+    } else {
+        Err(String::from("System error").into())
+    }
+}
+
+fn parse_float(spliter: &mut Split<char>) -> Result<Option<FLOAT>, Box<dyn Error + 'static>> {
+    if let Some(val) = spliter.next() {
+        log::debug!("{val}");
+        if is_missing_value(val, &[]) {
+            return Ok(None);
+        }
+        Ok(Some(val.trim().parse::<FLOAT>()?))
     } else {
         Err(String::from("System error").into())
     }
@@ -65,79 +106,83 @@ fn parse_datetime(spliter: &mut Split<char>) -> Result<NaiveDateTime, Box<dyn Er
     }
 }
 
-fn parse_time(spliter: &mut Split<char>) -> Result<NaiveTime, Box<dyn Error + 'static>> {
+fn parse_time(spliter: &mut Split<char>) -> Result<Option<NaiveTime>, Box<dyn Error + 'static>> {
     if let Some(val) = spliter.next() {
         log::debug!("{val}");
-        Ok(NaiveTime::parse_from_str(val, "%H%M")?)
+        if is_missing_value(val, &[]) {
+            return Ok(None);
+        }
+        Ok(Some(NaiveTime::parse_from_str(val.trim(), "%H%M")?))
     } else {
         Err(String::from("System error").into())
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBMinData {
-    station: String,
-    dkind: String,
-    dtime: NaiveDateTime, // local time
-    dd_p1: FLOAT,         // hPa, station pressure
-    dd_mmp2: FLOAT,       // hPa, sea level pressure
-    dd_t: FLOAT,          // degC, temperature
-    dd_td: FLOAT,         // degC, dew point temperature
-    dd_rh: FLOAT,         // %, relative humidity
-    dd_e: FLOAT,          // hPa, staturated vapor
-    dd_e_: FLOAT,         // hPa, vapor
-    dd_10d: INTEGER,      // vector, 10 min average wind direction
-    dd_f10: FLOAT,        // m/s, 10 min average wind speed
-    dd_xxd: INTEGER,      // vector, wind direction at max wind speed
-    dd_fxx: FLOAT,        // m/s, max wind speed
-    dd_rmn: FLOAT,        // mm, rain per min
-    dd_r: FLOAT,          // mm, rain per hour
-    dd_p1x: FLOAT,        // hPa, daily max pressure
-    ddp1xt: NaiveTime,    // local time, time at daily max pressure
-    dd_p1n: FLOAT,        // hPa, daily min pressure
-    ddp1nt: NaiveTime,    // local time, time at daily min pressure
-    dd_tx: FLOAT,         // degC, daily max temperature
-    dd_txt: NaiveTime,    // local time, time at daily max temperature
-    dd_tn: FLOAT,         // degC, daily min temperature
-    dd_tnt: NaiveTime,    // local time, time at daily min temperature
-    dd_tdx: FLOAT,        // degC, daily max dew point temperature
-    ddtdxt: NaiveTime,    // local time, time at daily max dew point temperature
-    dd_tdn: FLOAT,        // degC, daily min dew point temperature
-    ddtdnt: NaiveTime,    // local time, time at daily min dew point temperature
-    dd_rhx: FLOAT,        // %, daily max relative humidity
-    ddrhxt: NaiveTime,    // local time, time at daily max relative humidity
-    dd_rhn: FLOAT,        // %, daily min relative humidity
-    ddrhnt: NaiveTime,    // local time, time at daily min relative humidity
-    dd_ex: FLOAT,         // hPa, daily max vapor
-    dd_ext: NaiveTime,    // local time, time at daily max vapor
-    dd_en: FLOAT,         // %, daily min vapor
-    dd_ent: NaiveTime,    // local time, time at daily min vapor
-    dd_fx: FLOAT,         // m/s, daily max wind speed
-    dd_xd: INTEGER,       // vector, wind direction at daily max wind speed
-    dd_fxt: FLOAT,        // local time, time at daily max wind speed
-    dd_f10x: FLOAT,       // m/s, [dd_f10] daily max 10 min average wind speed
-    dd_10dx: INTEGER,     // vector, [dd_10d] wind direction at daily max 10 min average wind speed
-    ddf10xt: NaiveTime,   // loacl time, [ddf10xt] time at daily max 10 min average wind speed
-    dd_wd: FLOAT,         // m, average wind range
-    dd_tr: FLOAT,         // mm, daily rain
-    dd_rx10: FLOAT,       // mm, max rain with 10 min (10 分鐘最大累積雨量)
-    dd_x10t: NaiveTime,   // local time, time at max rain with 10 min
-    dd_rx60: FLOAT,       // mm, max rain with 60 min (60 分鐘最大累積雨量)
-    dd_x60t: NaiveTime,   // local time , time at max rain with 60 min
-    dd_tgr: FLOAT,        // MJ/m2, daily accumulation of solar radiation
-    dd_rad: FLOAT,        // KJ/m2, accumulation of solar radiation per min
-    dd_gr: FLOAT,         // MJ/m2, accumulation of solar radiaiton per hour
-    dd_tsh: FLOAT,        // Hr, daily accumulation of sunshine
-    dd_sh: FLOAT,         // Hr, accumulation of sunshine per hour
-    dd_t05: FLOAT,        // degC, 5 cm grassland temperature
-    dd_t00: FLOAT,        // degC, 0 cm grassland temperature
-    dd_st005: FLOAT,      // degC, 5 cm under ground temperature
-    dd_st010: FLOAT,      // degC, 10 cm under ground temperature
-    dd_st020: FLOAT,      // degC, 20 cm under ground temperature
-    dd_st030: FLOAT,      // degC, 30 cm under ground temperature
-    dd_st050: FLOAT,      // degC, 50 cm under ground temperature
-    dd_st100: FLOAT,      // degC, 100 cm under ground temperature
-    dd_sitest: String,    // station status code
+    pub station: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub dd_p1: Option<FLOAT>,       // hPa, station pressure
+    pub dd_mmp2: Option<FLOAT>,     // hPa, sea level pressure
+    pub dd_t: Option<FLOAT>,        // degC, temperature
+    pub dd_td: Option<FLOAT>,       // degC, dew point temperature
+    pub dd_rh: Option<FLOAT>,       // %, relative humidity
+    pub dd_e: Option<FLOAT>,        // hPa, staturated vapor
+    pub dd_e_: Option<FLOAT>,       // hPa, vapor
+    pub dd_10d: Option<INTEGER>,    // vector, 10 min average wind direction
+    pub dd_f10: Option<FLOAT>,      // m/s, 10 min average wind speed
+    pub dd_xxd: Option<INTEGER>,    // vector, wind direction at max wind speed
+    pub dd_fxx: Option<FLOAT>,      // m/s, max wind speed
+    pub dd_rmn: Option<FLOAT>,      // mm, rain per min
+    pub dd_r: Option<FLOAT>,        // mm, rain per hour
+    pub dd_p1x: Option<FLOAT>,      // hPa, daily max pressure
+    pub ddp1xt: Option<NaiveTime>,  // local time, time at daily max pressure
+    pub dd_p1n: Option<FLOAT>,      // hPa, daily min pressure
+    pub ddp1nt: Option<NaiveTime>,  // local time, time at daily min pressure
+    pub dd_tx: Option<FLOAT>,       // degC, daily max temperature
+    pub dd_txt: Option<NaiveTime>,  // local time, time at daily max temperature
+    pub dd_tn: Option<FLOAT>,       // degC, daily min temperature
+    pub dd_tnt: Option<NaiveTime>,  // local time, time at daily min temperature
+    pub dd_tdx: Option<FLOAT>,      // degC, daily max dew point temperature
+    pub ddtdxt: Option<NaiveTime>,  // local time, time at daily max dew point temperature
+    pub dd_tdn: Option<FLOAT>,      // degC, daily min dew point temperature
+    pub ddtdnt: Option<NaiveTime>,  // local time, time at daily min dew point temperature
+    pub dd_rhx: Option<FLOAT>,      // %, daily max relative humidity
+    pub ddrhxt: Option<NaiveTime>,  // local time, time at daily max relative humidity
+    pub dd_rhn: Option<FLOAT>,      // %, daily min relative humidity
+    pub ddrhnt: Option<NaiveTime>,  // local time, time at daily min relative humidity
+    pub dd_ex: Option<FLOAT>,       // hPa, daily max vapor
+    pub dd_ext: Option<NaiveTime>,  // local time, time at daily max vapor
+    pub dd_en: Option<FLOAT>,       // %, daily min vapor
+    pub dd_ent: Option<NaiveTime>,  // local time, time at daily min vapor
+    pub dd_fx: Option<FLOAT>,       // m/s, daily max wind speed
+    pub dd_xd: Option<INTEGER>,     // vector, wind direction at daily max wind speed
+    pub dd_fxt: Option<FLOAT>,      // local time, time at daily max wind speed
+    pub dd_f10x: Option<FLOAT>,     // m/s, [dd_f10] daily max 10 min average wind speed
+    pub dd_10dx: Option<INTEGER>, // vector, [dd_10d] wind direction at daily max 10 min average wind speed
+    pub ddf10xt: Option<NaiveTime>, // loacl time, [ddf10xt] time at daily max 10 min average wind speed
+    pub dd_wd: Option<FLOAT>,       // m, average wind range
+    pub dd_tr: Option<FLOAT>,       // mm, daily rain
+    pub dd_rx10: Option<FLOAT>,     // mm, max rain with 10 min (10 分鐘最大累積雨量)
+    pub dd_x10t: Option<NaiveTime>, // local time, time at max rain with 10 min
+    pub dd_rx60: Option<FLOAT>,     // mm, max rain with 60 min (60 分鐘最大累積雨量)
+    pub dd_x60t: Option<NaiveTime>, // local time , time at max rain with 60 min
+    pub dd_tgr: Option<FLOAT>,      // MJ/m2, daily accumulation of solar radiation
+    pub dd_rad: Option<FLOAT>,      // KJ/m2, accumulation of solar radiation per min
+    pub dd_gr: Option<FLOAT>,       // MJ/m2, accumulation of solar radiaiton per hour
+    pub dd_tsh: Option<FLOAT>,      // Hr, daily accumulation of sunshine
+    pub dd_sh: Option<FLOAT>,       // Hr, accumulation of sunshine per hour
+    pub dd_t05: Option<FLOAT>,      // degC, 5 cm grassland temperature
+    pub dd_t00: Option<FLOAT>,      // degC, 0 cm grassland temperature
+    pub dd_st005: Option<FLOAT>,    // degC, 5 cm under ground temperature
+    pub dd_st010: Option<FLOAT>,    // degC, 10 cm under ground temperature
+    pub dd_st020: Option<FLOAT>,    // degC, 20 cm under ground temperature
+    pub dd_st030: Option<FLOAT>,    // degC, 30 cm under ground temperature
+    pub dd_st050: Option<FLOAT>,    // degC, 50 cm under ground temperature
+    pub dd_st100: Option<FLOAT>,    // degC, 100 cm under ground temperature
+    pub dd_sitest: Option<String>,  // station status code
 }
 
 impl CWBMinData {
@@ -216,7 +261,7 @@ impl CWBMinData {
             dd_st030: parse_float(&mut words)?,
             dd_st050: parse_float(&mut words)?,
             dd_st100: parse_float(&mut words)?,
-            dd_sitest: parse_string(&mut words)?,
+            dd_sitest: parse_optional_string(&mut words)?,
         };
 
         // etx
@@ -228,88 +273,89 @@ impl CWBMinData {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBHourData {
-    station: String,
-    dkind: String,
-    dtime: NaiveDateTime, // local time
-    h_p1: FLOAT,          // hPa, station pressure
-    h_p2: FLOAT,          // hPa, sea level pressure
-    h_a: INTEGER,         // pressure tendency
-    h_pp: FLOAT,          // 3 hour pressure diff
-    h_e: FLOAT,           // hPa, staturated vapor
-    h_e_: FLOAT,          // hPa, vapor
-    h_t: FLOAT,           // degC, temperature
-    h_tx: FLOAT,          // degC, daily max temperature
-    h_tn: FLOAT,          // degC, daily min temperature
-    h_td: FLOAT,          // degC, dew point temperature
-    h_rh: FLOAT,          // %, relative humidity
-    h_dd: INTEGER,        // vector, average wind direction
-    h_ff: FLOAT,          // m/s, average wind speed
-    h_f10: FLOAT,         // m/s, max average wind speed
-    h_10d: INTEGER,       // vector, wind direction at max average wind speed
-    h_f10t: NaiveTime,    // local time, time at max average wind speed
-    h_fx: FLOAT,          // m/s, max wind speed
-    h_xd: INTEGER,        // vector, wind direction at max wind speed
-    h_fxt: NaiveTime,     // local time, time at max wind speed
-    h_xd3: INTEGER,       // vector, wind direction at 3 hour max wind speed
-    h_fx3: FLOAT,         // m/s, 3 hour max wind speed
-    h_r: FLOAT,           // mm, rain per hour
-    h_gmt: FLOAT,         // mm, gmt rain
-    h_3r: FLOAT,          // mm, rain in 3 hours
-    h_6r: FLOAT,          // mm, rain in 6 hours
-    h_9r: FLOAT,          // mm, rain in 9 hours
-    h_12r: FLOAT,         // mm, rain in 12 hours
-    h_24r: FLOAT,         // mm, rain in 24 hours in local time
-    h_24rrr: FLOAT,       // mm, yesterday rain
-    h_gr: FLOAT,          // MJ/m2, accumulation of solar radiaiton per hour
-    h_sh: FLOAT,          // Hr, accumulation of sunshine per hour
-    h_t005: FLOAT,        // degC, 5 cm grassland temperature
-    h_t005x: FLOAT,       // degC, 5 cm grassland max temperature
-    h_t005xt: NaiveTime,  // local time, time at 5 cm grassland max temperature
-    h_t005n: FLOAT,       // degC, 5 cm grassland min temperature
-    h_t005nt: NaiveTime,  // local time, time at 5 cm grassland min temperature
-    h_t000: FLOAT,        // degC, 0 cm grassland temperature
-    h_t000x: FLOAT,       // degC, 0 cm grassland max temperature
-    h_t000xt: NaiveTime,  // local time, time at 0 cm grassland max temperature
-    h_t000n: FLOAT,       // degC, 0 cm grassland min temperature
-    h_t000nt: NaiveTime,  // local time, time at 0 cm grassland min temperature
-    h_st005: FLOAT,       // degC, 5 cm under ground temperature
-    h_st005x: FLOAT,      // degC, 5 cm under ground max temperature
-    h_st005xt: NaiveTime, // local time, time at 5 cm under ground max temperature
-    h_st005n: FLOAT,      // degC, 5 cm under ground min temperature
-    h_st005nt: NaiveTime, // local time, time at 5 cm under ground min temperature
-    h_st010: FLOAT,       // degC, 10 cm under ground temperature
-    h_st010x: FLOAT,      // degC, 10 cm under ground max temperature
-    h_st010xt: NaiveTime, // local time, time at 10 cm under ground max temperature
-    h_st010n: FLOAT,      // degC, 10 cm under ground min temperature
-    h_st010nt: NaiveTime, // local time, time at 10 cm under ground min temperature
-    h_st020: FLOAT,       // degC, 20 cm under ground temperature
-    h_st020x: FLOAT,      // degC, 20 cm under ground max temperature
-    h_st020xt: NaiveTime, // local time, time at 20 cm under ground max temperature
-    h_st020n: FLOAT,      // degC, 20 cm under ground min temperature
-    h_st020nt: NaiveTime, // local time, time at 20 cm under ground min temperature
-    h_st030: FLOAT,       // degC, 30 cm under ground temperature
-    h_st030x: FLOAT,      // degC, 30 cm under ground max temperature
-    h_st030xt: NaiveTime, // local time, time at 30 cm under ground max temperature
-    h_st030n: FLOAT,      // degC, 30 cm under ground min temperature
-    h_st030nt: NaiveTime, // local time, time at 30 cm under ground min temperature
-    h_st050: FLOAT,       // degC, 50 cm under ground temperature
-    h_st050x: FLOAT,      // degC, 50 cm under ground max temperature
-    h_st050xt: NaiveTime, // local time, time at 50 cm under ground max temperature
-    h_st050n: FLOAT,      // degC, 50 cm under ground min temperature
-    h_st050nt: NaiveTime, // local time, time at 50 cm under ground min temperature
-    h_st100: FLOAT,       // degC, 100 cm under ground temperature
-    h_st100x: FLOAT,      // degC, 100 cm under ground max temperature
-    h_st100xt: NaiveTime, // local time, time at 100 cm under ground max temperature
-    h_st100n: FLOAT,      // degC, 100 cm under ground min temperature
-    h_st100nt: NaiveTime, // local time, time at 100 cm under ground min temperature
+    pub station: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub h_p1: Option<FLOAT>,          // hPa, station pressure
+    pub h_p2: Option<FLOAT>,          // hPa, sea level pressure
+    pub h_a: Option<INTEGER>,         // pressure tendency
+    pub h_pp: Option<FLOAT>,          // 3 hour pressure diff
+    pub h_e: Option<FLOAT>,           // hPa, staturated vapor
+    pub h_e_: Option<FLOAT>,          // hPa, vapor
+    pub h_t: Option<FLOAT>,           // degC, temperature
+    pub h_tx: Option<FLOAT>,          // degC, daily max temperature
+    pub h_tn: Option<FLOAT>,          // degC, daily min temperature
+    pub h_td: Option<FLOAT>,          // degC, dew point temperature
+    pub h_rh: Option<FLOAT>,          // %, relative humidity
+    pub h_dd: Option<INTEGER>,        // vector, average wind direction
+    pub h_ff: Option<FLOAT>,          // m/s, average wind speed
+    pub h_f10: Option<FLOAT>,         // m/s, max average wind speed
+    pub h_10d: Option<INTEGER>,       // vector, wind direction at max average wind speed
+    pub h_f10t: Option<NaiveTime>,    // local time, time at max average wind speed
+    pub h_fx: Option<FLOAT>,          // m/s, max wind speed
+    pub h_xd: Option<INTEGER>,        // vector, wind direction at max wind speed
+    pub h_fxt: Option<NaiveTime>,     // local time, time at max wind speed
+    pub h_xd3: Option<INTEGER>,       // vector, wind direction at 3 hour max wind speed
+    pub h_fx3: Option<FLOAT>,         // m/s, 3 hour max wind speed
+    pub h_r: Option<FLOAT>,           // mm, rain per hour
+    pub h_gmt: Option<FLOAT>,         // mm, gmt rain
+    pub h_3r: Option<FLOAT>,          // mm, rain in 3 hours
+    pub h_6r: Option<FLOAT>,          // mm, rain in 6 hours
+    pub h_9r: Option<FLOAT>,          // mm, rain in 9 hours
+    pub h_12r: Option<FLOAT>,         // mm, rain in 12 hours
+    pub h_24r: Option<FLOAT>,         // mm, rain in 24 hours in local time
+    pub h_24rrr: Option<FLOAT>,       // mm, yesterday rain
+    pub h_gr: Option<FLOAT>,          // MJ/m2, accumulation of solar radiaiton per hour
+    pub h_sh: Option<FLOAT>,          // Hr, accumulation of sunshine per hour
+    pub h_t005: Option<FLOAT>,        // degC, 5 cm grassland temperature
+    pub h_t005x: Option<FLOAT>,       // degC, 5 cm grassland max temperature
+    pub h_t005xt: Option<NaiveTime>,  // local time, time at 5 cm grassland max temperature
+    pub h_t005n: Option<FLOAT>,       // degC, 5 cm grassland min temperature
+    pub h_t005nt: Option<NaiveTime>,  // local time, time at 5 cm grassland min temperature
+    pub h_t000: Option<FLOAT>,        // degC, 0 cm grassland temperature
+    pub h_t000x: Option<FLOAT>,       // degC, 0 cm grassland max temperature
+    pub h_t000xt: Option<NaiveTime>,  // local time, time at 0 cm grassland max temperature
+    pub h_t000n: Option<FLOAT>,       // degC, 0 cm grassland min temperature
+    pub h_t000nt: Option<NaiveTime>,  // local time, time at 0 cm grassland min temperature
+    pub h_st005: Option<FLOAT>,       // degC, 5 cm under ground temperature
+    pub h_st005x: Option<FLOAT>,      // degC, 5 cm under ground max temperature
+    pub h_st005xt: Option<NaiveTime>, // local time, time at 5 cm under ground max temperature
+    pub h_st005n: Option<FLOAT>,      // degC, 5 cm under ground min temperature
+    pub h_st005nt: Option<NaiveTime>, // local time, time at 5 cm under ground min temperature
+    pub h_st010: Option<FLOAT>,       // degC, 10 cm under ground temperature
+    pub h_st010x: Option<FLOAT>,      // degC, 10 cm under ground max temperature
+    pub h_st010xt: Option<NaiveTime>, // local time, time at 10 cm under ground max temperature
+    pub h_st010n: Option<FLOAT>,      // degC, 10 cm under ground min temperature
+    pub h_st010nt: Option<NaiveTime>, // local time, time at 10 cm under ground min temperature
+    pub h_st020: Option<FLOAT>,       // degC, 20 cm under ground temperature
+    pub h_st020x: Option<FLOAT>,      // degC, 20 cm under ground max temperature
+    pub h_st020xt: Option<NaiveTime>, // local time, time at 20 cm under ground max temperature
+    pub h_st020n: Option<FLOAT>,      // degC, 20 cm under ground min temperature
+    pub h_st020nt: Option<NaiveTime>, // local time, time at 20 cm under ground min temperature
+    pub h_st030: Option<FLOAT>,       // degC, 30 cm under ground temperature
+    pub h_st030x: Option<FLOAT>,      // degC, 30 cm under ground max temperature
+    pub h_st030xt: Option<NaiveTime>, // local time, time at 30 cm under ground max temperature
+    pub h_st030n: Option<FLOAT>,      // degC, 30 cm under ground min temperature
+    pub h_st030nt: Option<NaiveTime>, // local time, time at 30 cm under ground min temperature
+    pub h_st050: Option<FLOAT>,       // degC, 50 cm under ground temperature
+    pub h_st050x: Option<FLOAT>,      // degC, 50 cm under ground max temperature
+    pub h_st050xt: Option<NaiveTime>, // local time, time at 50 cm under ground max temperature
+    pub h_st050n: Option<FLOAT>,      // degC, 50 cm under ground min temperature
+    pub h_st050nt: Option<NaiveTime>, // local time, time at 50 cm under ground min temperature
+    pub h_st100: Option<FLOAT>,       // degC, 100 cm under ground temperature
+    pub h_st100x: Option<FLOAT>,      // degC, 100 cm under ground max temperature
+    pub h_st100xt: Option<NaiveTime>, // local time, time at 100 cm under ground max temperature
+    pub h_st100n: Option<FLOAT>,      // degC, 100 cm under ground min temperature
+    pub h_st100nt: Option<NaiveTime>, // local time, time at 100 cm under ground min temperature
 }
 
 impl CWBHourData {
     pub fn parse_from_str(data: &str) -> Result<Self, Box<dyn Error + 'static>> {
         let mut words = data.split(',');
-        if words.nth(2) != Some("HR") && words.nth(2) != Some("HH") {
+        if !matches!(words.nth(2), Some("HR") | Some("HH")) {
             return Err(String::from("Invalid").into());
         }
 
@@ -406,103 +452,104 @@ impl CWBHourData {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBDayData {
-    station: String,
-    dkind: String,
-    dtime: NaiveDateTime, // local time
-    d_mp1: FLOAT,         // hPa, station pressure
-    d_dp1: FLOAT,         // hPa, pressure diff
-    d_p1x: FLOAT,         // hPa, max station pressure
-    d_p1xt: NaiveTime,    // local time, time at max station pressure
-    d_p1n: FLOAT,         // hPa, min station pressure
-    d_p1nt: NaiveTime,    // local time, time at min station pressure
-    d_mp2: FLOAT,         // hPa, sea level pressure
-    d_mt: FLOAT,          // degC, temperature
-    d_dt: FLOAT,          // degC, temperature diff
-    d_tx: FLOAT,          // degC, max temperature
-    d_txt: NaiveTime,     // local time, time at max temperature
-    d_tn: FLOAT,          // degC, min temperature
-    d_tnt: NaiveTime,     // local time, time at min temperature
-    d_mtd: FLOAT,         // degC, dew point temperature
-    d_dtd: FLOAT,         // degC, dew point temperature diff
-    d_tdx: FLOAT,         // degC, max dew point temperature
-    d_tdxt: NaiveTime,    // local time, time at max dew point temperature
-    d_tdn: FLOAT,         // degC, min dew point temperature
-    d_tdnt: NaiveTime,    // local time, time at min dew point temperature
-    d_mrh: FLOAT,         // %, relative humidity
-    d_rhx: FLOAT,         // %, max relative humidity
-    d_rhxt: NaiveTime,    // local time, time at max relative humidity
-    d_rhn: FLOAT,         // %, min relative humidity
-    d_rhnt: NaiveTime,    // local time, time at min relative humidity
-    d_me_: FLOAT,         // hPa, vapor
-    d_ex: FLOAT,          // hPa, max staturated vapor
-    d_ext: NaiveTime,     // local time, time at max staturated vapor
-    d_en: FLOAT,          // hPa, min staturated vapor
-    d_ent: NaiveTime,     // local time, time at min staturated vapor
-    d_me: FLOAT,          // hPa, staturated vapor
-    d_mwd: INTEGER,       // vector, average wind direction
-    d_mws: FLOAT,         // m/s, average wind speed
-    d_fx: FLOAT,          // m/s, max wind speed
-    d_xd: INTEGER,        // vector, wind direction at max wind speed
-    d_fxt: NaiveTime,     // local time, time at max wind speed
-    d_f10: FLOAT,         // m/s, max average wind speed
-    d_10d: INTEGER,       // vector, wind direction at max average wind speed
-    d_f10t: NaiveTime,    // local time, time at max average wind speed
-    d_wd: FLOAT,          // m, wind range
-    d_tr: FLOAT,          // mm, rain
-    d_rx10: FLOAT,        // mm, max 10 min rain
-    d_x10t: NaiveTime,    // local time, time at max 10 min rain
-    d_rx60: FLOAT,        // mm, max 60 min rain
-    d_x60t: NaiveTime,    // local time, time at max 60 min rain
-    d_tgr: FLOAT,         // MJ/m2, accumulation of solar radiaiton
-    d_tsh: FLOAT,         // Hr, accumulation of sunshine
-    d_t005: FLOAT,        // degC, 5 cm grassland temperature
-    d_t005x: FLOAT,       // degC, 5 cm grassland max temperature
-    d_t005xt: NaiveTime,  // local time, time at 5 cm grassland max temperature
-    d_t005n: FLOAT,       // degC, 5 cm grassland min temperature
-    d_t005nt: NaiveTime,  // local time, time at 5 cm grassland min temperature
-    d_t000: FLOAT,        // degC, 0 cm grassland temperature
-    d_t000x: FLOAT,       // degC, 0 cm grassland max temperature
-    d_t000xt: NaiveTime,  // local time, time at 0 cm grassland max temperature
-    d_t000n: FLOAT,       // degC, 0 cm grassland min temperature
-    d_t000nt: NaiveTime,  // local time, time at 0 cm grassland min temperature
-    d_st005: FLOAT,       // degC, 5 cm under ground temperature
-    d_st005x: FLOAT,      // degC, 5 cm under ground max temperature
-    d_st005xt: NaiveTime, // local time, time at 5 cm under ground max temperature
-    d_st005n: FLOAT,      // degC, 5 cm under ground min temperature
-    d_st005nt: NaiveTime, // local time, time at 5 cm under ground min temperature
-    d_st010: FLOAT,       // degC, 10 cm under ground temperature
-    d_st010x: FLOAT,      // degC, 10 cm under ground max temperature
-    d_st010xt: NaiveTime, // local time, time at 10 cm under ground max temperature
-    d_st010n: FLOAT,      // degC, 10 cm under ground min temperature
-    d_st010nt: NaiveTime, // local time, time at 10 cm under ground min temperature
-    d_st020: FLOAT,       // degC, 20 cm under ground temperature
-    d_st020x: FLOAT,      // degC, 20 cm under ground max temperature
-    d_st020xt: NaiveTime, // local time, time at 20 cm under ground max temperature
-    d_st020n: FLOAT,      // degC, 20 cm under ground min temperature
-    d_st020nt: NaiveTime, // local time, time at 20 cm under ground min temperature
-    d_st030: FLOAT,       // degC, 30 cm under ground temperature
-    d_st030x: FLOAT,      // degC, 30 cm under ground max temperature
-    d_st030xt: NaiveTime, // local time, time at 30 cm under ground max temperature
-    d_st030n: FLOAT,      // degC, 30 cm under ground min temperature
-    d_st030nt: NaiveTime, // local time, time at 30 cm under ground min temperature
-    d_st050: FLOAT,       // degC, 50 cm under ground temperature
-    d_st050x: FLOAT,      // degC, 50 cm under ground max temperature
-    d_st050xt: NaiveTime, // local time, time at 50 cm under ground max temperature
-    d_st050n: FLOAT,      // degC, 50 cm under ground min temperature
-    d_st050nt: NaiveTime, // local time, time at 50 cm under ground min temperature
-    d_st100: FLOAT,       // degC, 100 cm under ground temperature
-    d_st100x: FLOAT,      // degC, 100 cm under ground max temperature
-    d_st100xt: NaiveTime, // local time, time at 100 cm under ground max temperature
-    d_st100n: FLOAT,      // degC, 100 cm under ground min temperature
-    d_st100nt: NaiveTime, // local time, time at 100 cm under ground min temperature
+    pub station: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub d_mp1: Option<FLOAT>,         // hPa, station pressure
+    pub d_dp1: Option<FLOAT>,         // hPa, pressure diff
+    pub d_p1x: Option<FLOAT>,         // hPa, max station pressure
+    pub d_p1xt: Option<NaiveTime>,    // local time, time at max station pressure
+    pub d_p1n: Option<FLOAT>,         // hPa, min station pressure
+    pub d_p1nt: Option<NaiveTime>,    // local time, time at min station pressure
+    pub d_mp2: Option<FLOAT>,         // hPa, sea level pressure
+    pub d_mt: Option<FLOAT>,          // degC, temperature
+    pub d_dt: Option<FLOAT>,          // degC, temperature diff
+    pub d_tx: Option<FLOAT>,          // degC, max temperature
+    pub d_txt: Option<NaiveTime>,     // local time, time at max temperature
+    pub d_tn: Option<FLOAT>,          // degC, min temperature
+    pub d_tnt: Option<NaiveTime>,     // local time, time at min temperature
+    pub d_mtd: Option<FLOAT>,         // degC, dew point temperature
+    pub d_dtd: Option<FLOAT>,         // degC, dew point temperature diff
+    pub d_tdx: Option<FLOAT>,         // degC, max dew point temperature
+    pub d_tdxt: Option<NaiveTime>,    // local time, time at max dew point temperature
+    pub d_tdn: Option<FLOAT>,         // degC, min dew point temperature
+    pub d_tdnt: Option<NaiveTime>,    // local time, time at min dew point temperature
+    pub d_mrh: Option<FLOAT>,         // %, relative humidity
+    pub d_rhx: Option<FLOAT>,         // %, max relative humidity
+    pub d_rhxt: Option<NaiveTime>,    // local time, time at max relative humidity
+    pub d_rhn: Option<FLOAT>,         // %, min relative humidity
+    pub d_rhnt: Option<NaiveTime>,    // local time, time at min relative humidity
+    pub d_me_: Option<FLOAT>,         // hPa, vapor
+    pub d_ex: Option<FLOAT>,          // hPa, max staturated vapor
+    pub d_ext: Option<NaiveTime>,     // local time, time at max staturated vapor
+    pub d_en: Option<FLOAT>,          // hPa, min staturated vapor
+    pub d_ent: Option<NaiveTime>,     // local time, time at min staturated vapor
+    pub d_me: Option<FLOAT>,          // hPa, staturated vapor
+    pub d_mwd: Option<INTEGER>,       // vector, average wind direction
+    pub d_mws: Option<FLOAT>,         // m/s, average wind speed
+    pub d_fx: Option<FLOAT>,          // m/s, max wind speed
+    pub d_xd: Option<INTEGER>,        // vector, wind direction at max wind speed
+    pub d_fxt: Option<NaiveTime>,     // local time, time at max wind speed
+    pub d_f10: Option<FLOAT>,         // m/s, max average wind speed
+    pub d_10d: Option<INTEGER>,       // vector, wind direction at max average wind speed
+    pub d_f10t: Option<NaiveTime>,    // local time, time at max average wind speed
+    pub d_wd: Option<FLOAT>,          // m, wind range
+    pub d_tr: Option<FLOAT>,          // mm, rain
+    pub d_rx10: Option<FLOAT>,        // mm, max 10 min rain
+    pub d_x10t: Option<NaiveTime>,    // local time, time at max 10 min rain
+    pub d_rx60: Option<FLOAT>,        // mm, max 60 min rain
+    pub d_x60t: Option<NaiveTime>,    // local time, time at max 60 min rain
+    pub d_tgr: Option<FLOAT>,         // MJ/m2, accumulation of solar radiaiton
+    pub d_tsh: Option<FLOAT>,         // Hr, accumulation of sunshine
+    pub d_t005: Option<FLOAT>,        // degC, 5 cm grassland temperature
+    pub d_t005x: Option<FLOAT>,       // degC, 5 cm grassland max temperature
+    pub d_t005xt: Option<NaiveTime>,  // local time, time at 5 cm grassland max temperature
+    pub d_t005n: Option<FLOAT>,       // degC, 5 cm grassland min temperature
+    pub d_t005nt: Option<NaiveTime>,  // local time, time at 5 cm grassland min temperature
+    pub d_t000: Option<FLOAT>,        // degC, 0 cm grassland temperature
+    pub d_t000x: Option<FLOAT>,       // degC, 0 cm grassland max temperature
+    pub d_t000xt: Option<NaiveTime>,  // local time, time at 0 cm grassland max temperature
+    pub d_t000n: Option<FLOAT>,       // degC, 0 cm grassland min temperature
+    pub d_t000nt: Option<NaiveTime>,  // local time, time at 0 cm grassland min temperature
+    pub d_st005: Option<FLOAT>,       // degC, 5 cm under ground temperature
+    pub d_st005x: Option<FLOAT>,      // degC, 5 cm under ground max temperature
+    pub d_st005xt: Option<NaiveTime>, // local time, time at 5 cm under ground max temperature
+    pub d_st005n: Option<FLOAT>,      // degC, 5 cm under ground min temperature
+    pub d_st005nt: Option<NaiveTime>, // local time, time at 5 cm under ground min temperature
+    pub d_st010: Option<FLOAT>,       // degC, 10 cm under ground temperature
+    pub d_st010x: Option<FLOAT>,      // degC, 10 cm under ground max temperature
+    pub d_st010xt: Option<NaiveTime>, // local time, time at 10 cm under ground max temperature
+    pub d_st010n: Option<FLOAT>,      // degC, 10 cm under ground min temperature
+    pub d_st010nt: Option<NaiveTime>, // local time, time at 10 cm under ground min temperature
+    pub d_st020: Option<FLOAT>,       // degC, 20 cm under ground temperature
+    pub d_st020x: Option<FLOAT>,      // degC, 20 cm under ground max temperature
+    pub d_st020xt: Option<NaiveTime>, // local time, time at 20 cm under ground max temperature
+    pub d_st020n: Option<FLOAT>,      // degC, 20 cm under ground min temperature
+    pub d_st020nt: Option<NaiveTime>, // local time, time at 20 cm under ground min temperature
+    pub d_st030: Option<FLOAT>,       // degC, 30 cm under ground temperature
+    pub d_st030x: Option<FLOAT>,      // degC, 30 cm under ground max temperature
+    pub d_st030xt: Option<NaiveTime>, // local time, time at 30 cm under ground max temperature
+    pub d_st030n: Option<FLOAT>,      // degC, 30 cm under ground min temperature
+    pub d_st030nt: Option<NaiveTime>, // local time, time at 30 cm under ground min temperature
+    pub d_st050: Option<FLOAT>,       // degC, 50 cm under ground temperature
+    pub d_st050x: Option<FLOAT>,      // degC, 50 cm under ground max temperature
+    pub d_st050xt: Option<NaiveTime>, // local time, time at 50 cm under ground max temperature
+    pub d_st050n: Option<FLOAT>,      // degC, 50 cm under ground min temperature
+    pub d_st050nt: Option<NaiveTime>, // local time, time at 50 cm under ground min temperature
+    pub d_st100: Option<FLOAT>,       // degC, 100 cm under ground temperature
+    pub d_st100x: Option<FLOAT>,      // degC, 100 cm under ground max temperature
+    pub d_st100xt: Option<NaiveTime>, // local time, time at 100 cm under ground max temperature
+    pub d_st100n: Option<FLOAT>,      // degC, 100 cm under ground min temperature
+    pub d_st100nt: Option<NaiveTime>, // local time, time at 100 cm under ground min temperature
 }
 
 impl CWBDayData {
     pub fn parse_from_str(data: &str) -> Result<Self, Box<dyn Error + 'static>> {
         let mut words = data.split(',');
-        if words.nth(2) != Some("DY") && words.nth(2) != Some("DD") {
+        if !matches!(words.nth(2), Some("DY") | Some("DD")) {
             return Err(String::from("Invalid").into());
         }
 
@@ -614,24 +661,25 @@ impl CWBDayData {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBSoilMinData {
-    stationid: String,
-    dkind: String,
-    dtime: NaiveDateTime, // local time
-    dd_vmc010: FLOAT,     // %,   0-10  cm soil water contain
-    dd_vmc020: FLOAT,     // %,  10-20  cm soil water contain
-    dd_vmc030: FLOAT,     // %,  20-30  cm soil water contain
-    dd_vmc040: FLOAT,     // %,  30-40  cm soil water contain
-    dd_vmc050: FLOAT,     // %,  40-50  cm soil water contain
-    dd_vmc060: FLOAT,     // %,  50-60  cm soil water contain
-    dd_vmc070: FLOAT,     // %,  60-70  cm soil water contain
-    dd_vmc080: FLOAT,     // %,  70-80  cm soil water contain
-    dd_vmc090: FLOAT,     // %,  80-90  cm soil water contain
-    dd_vmc100: FLOAT,     // %,  90-100 cm soil water contain
-    dd_vmc110: FLOAT,     // %, 100-110 cm soil water contain
-    dd_vmc120: FLOAT,     // %, 110-120 cm soil water contain
-    dd_sitest: String,    //  station status code
+    pub stationid: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub dd_vmc010: Option<FLOAT>,  // %,   0-10  cm soil water contain
+    pub dd_vmc020: Option<FLOAT>,  // %,  10-20  cm soil water contain
+    pub dd_vmc030: Option<FLOAT>,  // %,  20-30  cm soil water contain
+    pub dd_vmc040: Option<FLOAT>,  // %,  30-40  cm soil water contain
+    pub dd_vmc050: Option<FLOAT>,  // %,  40-50  cm soil water contain
+    pub dd_vmc060: Option<FLOAT>,  // %,  50-60  cm soil water contain
+    pub dd_vmc070: Option<FLOAT>,  // %,  60-70  cm soil water contain
+    pub dd_vmc080: Option<FLOAT>,  // %,  70-80  cm soil water contain
+    pub dd_vmc090: Option<FLOAT>,  // %,  80-90  cm soil water contain
+    pub dd_vmc100: Option<FLOAT>,  // %,  90-100 cm soil water contain
+    pub dd_vmc110: Option<FLOAT>,  // %, 100-110 cm soil water contain
+    pub dd_vmc120: Option<FLOAT>,  // %, 110-120 cm soil water contain
+    pub dd_sitest: Option<String>, //  station status code
 }
 
 impl CWBSoilMinData {
@@ -664,7 +712,7 @@ impl CWBSoilMinData {
             dd_vmc100: parse_float(&mut words)?,
             dd_vmc110: parse_float(&mut words)?,
             dd_vmc120: parse_float(&mut words)?,
-            dd_sitest: parse_string(&mut words)?,
+            dd_sitest: parse_optional_string(&mut words)?,
         };
 
         // etx
@@ -676,71 +724,72 @@ impl CWBSoilMinData {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBSoilHourData {
-    stationid: String,
-    dkind: String,
-    dtime: NaiveDateTime,  // local time
-    h_vmc010: FLOAT,       // %,   0-10  cm soil water contain
-    h_vmc010x: FLOAT,      // %,   0-10  cm max soil water contain
-    h_vmc010xt: NaiveTime, // local time, time at 0-10 cm max soil water contain
-    h_vmc010n: FLOAT,      // %,   0-10  cm min soil water contain
-    h_vmc010nt: NaiveTime, // local time, time at 0-10 cm min soil water contain
-    h_vmc020: FLOAT,       // %,  10-20  cm soil water contain
-    h_vmc020x: FLOAT,      // %,  10-20  cm max soil water contain
-    h_vmc020xt: NaiveTime, // local time, time at 10-20 cm max soil water contain
-    h_vmc020n: FLOAT,      // %,  10-20  cm min soil water contain
-    h_vmc020nt: NaiveTime, // local time, time at 10-20 cm min soil water contain
-    h_vmc030: FLOAT,       // %,  20-30  cm soil water contain
-    h_vmc030x: FLOAT,      // %,  20-30  cm max soil water contain
-    h_vmc030xt: NaiveTime, // local time, time at 20-30 cm max soil water contain
-    h_vmc030n: FLOAT,      // %,  20-30  cm min soil water contain
-    h_vmc030nt: NaiveTime, // local time, time at 20-30 cm min soil water contain
-    h_vmc040: FLOAT,       // %,  30-40  cm soil water contain
-    h_vmc040x: FLOAT,      // %,  30-40  cm max soil water contain
-    h_vmc040xt: NaiveTime, // local time, time at 30-40 cm max soil water contain
-    h_vmc040n: FLOAT,      // %,  30-40  cm min soil water contain
-    h_vmc040nt: NaiveTime, // local time, time at 30-40 cm min soil water contain
-    h_vmc050: FLOAT,       // %,  40-50  cm soil water contain
-    h_vmc050x: FLOAT,      // %,  40-50  cm max soil water contain
-    h_vmc050xt: NaiveTime, // local time, time at 40-50 cm max soil water contain
-    h_vmc050n: FLOAT,      // %,  40-50  cm min soil water contain
-    h_vmc050nt: NaiveTime, // local time, time at 40-50 cm min soil water contain
-    h_vmc060: FLOAT,       // %,  50-60  cm soil water contain
-    h_vmc060x: FLOAT,      // %,  50-60  cm max soil water contain
-    h_vmc060xt: NaiveTime, // local time, time at 50-60 cm max soil water contain
-    h_vmc060n: FLOAT,      // %,  50-60  cm min soil water contain
-    h_vmc060nt: NaiveTime, // local time, time at 50-60 cm min soil water contain
-    h_vmc070: FLOAT,       // %,  60-70  cm soil water contain
-    h_vmc070x: FLOAT,      // %,  60-70  cm max soil water contain
-    h_vmc070xt: NaiveTime, // local time, time at 60-70 cm max soil water contain
-    h_vmc070n: FLOAT,      // %,  60-70  cm min soil water contain
-    h_vmc070nt: NaiveTime, // local time, time at 60-70 cm min soil water contain
-    h_vmc080: FLOAT,       // %,  70-80  cm soil water contain
-    h_vmc080x: FLOAT,      // %,  70-80  cm max soil water contain
-    h_vmc080xt: NaiveTime, // local time, time at 70-80 cm max soil water contain
-    h_vmc080n: FLOAT,      // %,  70-80  cm min soil water contain
-    h_vmc080nt: NaiveTime, // local time, time at 70-80 cm min soil water contain
-    h_vmc090: FLOAT,       // %,  80-90  cm soil water contain
-    h_vmc090x: FLOAT,      // %,  80-90  cm max soil water contain
-    h_vmc090xt: NaiveTime, // local time, time at 80-90 cm max soil water contain
-    h_vmc090n: FLOAT,      // %,  80-90  cm min soil water contain
-    h_vmc090nt: NaiveTime, // local time, time at 80-90 cm min soil water contain
-    h_vmc100: FLOAT,       // %,  90-100 cm soil water contain
-    h_vmc100x: FLOAT,      // %,  90-100 cm max soil water contain
-    h_vmc100xt: NaiveTime, // local time, time at 90-100 cm max soil water contain
-    h_vmc100n: FLOAT,      // %,  90-100 cm min soil water contain
-    h_vmc100nt: NaiveTime, // local time, time at 90-100 cm min soil water contain
-    h_vmc110: FLOAT,       // %, 100-110 cm soil water contain
-    h_vmc110x: FLOAT,      // %, 100-110  cm max soil water contain
-    h_vmc110xt: NaiveTime, // local time, time at 100-110 cm max soil water contain
-    h_vmc110n: FLOAT,      // %, 100-110  cm min soil water contain
-    h_vmc110nt: NaiveTime, // local time, time at 100-110 cm min soil water contain
-    h_vmc120: FLOAT,       // %, 110-120 cm soil water contain
-    h_vmc120x: FLOAT,      // %, 110-120 cm max soil water contain
-    h_vmc120xt: NaiveTime, // local time, time at 110-120 cm max soil water contain
-    h_vmc120n: FLOAT,      // %, 110-120 cm min soil water contain
-    h_vmc120nt: NaiveTime, // local time, time at 110-120 cm min soil water contain
+    pub stationid: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub h_vmc010: Option<FLOAT>,       // %,   0-10  cm soil water contain
+    pub h_vmc010x: Option<FLOAT>,      // %,   0-10  cm max soil water contain
+    pub h_vmc010xt: Option<NaiveTime>, // local time, time at 0-10 cm max soil water contain
+    pub h_vmc010n: Option<FLOAT>,      // %,   0-10  cm min soil water contain
+    pub h_vmc010nt: Option<NaiveTime>, // local time, time at 0-10 cm min soil water contain
+    pub h_vmc020: Option<FLOAT>,       // %,  10-20  cm soil water contain
+    pub h_vmc020x: Option<FLOAT>,      // %,  10-20  cm max soil water contain
+    pub h_vmc020xt: Option<NaiveTime>, // local time, time at 10-20 cm max soil water contain
+    pub h_vmc020n: Option<FLOAT>,      // %,  10-20  cm min soil water contain
+    pub h_vmc020nt: Option<NaiveTime>, // local time, time at 10-20 cm min soil water contain
+    pub h_vmc030: Option<FLOAT>,       // %,  20-30  cm soil water contain
+    pub h_vmc030x: Option<FLOAT>,      // %,  20-30  cm max soil water contain
+    pub h_vmc030xt: Option<NaiveTime>, // local time, time at 20-30 cm max soil water contain
+    pub h_vmc030n: Option<FLOAT>,      // %,  20-30  cm min soil water contain
+    pub h_vmc030nt: Option<NaiveTime>, // local time, time at 20-30 cm min soil water contain
+    pub h_vmc040: Option<FLOAT>,       // %,  30-40  cm soil water contain
+    pub h_vmc040x: Option<FLOAT>,      // %,  30-40  cm max soil water contain
+    pub h_vmc040xt: Option<NaiveTime>, // local time, time at 30-40 cm max soil water contain
+    pub h_vmc040n: Option<FLOAT>,      // %,  30-40  cm min soil water contain
+    pub h_vmc040nt: Option<NaiveTime>, // local time, time at 30-40 cm min soil water contain
+    pub h_vmc050: Option<FLOAT>,       // %,  40-50  cm soil water contain
+    pub h_vmc050x: Option<FLOAT>,      // %,  40-50  cm max soil water contain
+    pub h_vmc050xt: Option<NaiveTime>, // local time, time at 40-50 cm max soil water contain
+    pub h_vmc050n: Option<FLOAT>,      // %,  40-50  cm min soil water contain
+    pub h_vmc050nt: Option<NaiveTime>, // local time, time at 40-50 cm min soil water contain
+    pub h_vmc060: Option<FLOAT>,       // %,  50-60  cm soil water contain
+    pub h_vmc060x: Option<FLOAT>,      // %,  50-60  cm max soil water contain
+    pub h_vmc060xt: Option<NaiveTime>, // local time, time at 50-60 cm max soil water contain
+    pub h_vmc060n: Option<FLOAT>,      // %,  50-60  cm min soil water contain
+    pub h_vmc060nt: Option<NaiveTime>, // local time, time at 50-60 cm min soil water contain
+    pub h_vmc070: Option<FLOAT>,       // %,  60-70  cm soil water contain
+    pub h_vmc070x: Option<FLOAT>,      // %,  60-70  cm max soil water contain
+    pub h_vmc070xt: Option<NaiveTime>, // local time, time at 60-70 cm max soil water contain
+    pub h_vmc070n: Option<FLOAT>,      // %,  60-70  cm min soil water contain
+    pub h_vmc070nt: Option<NaiveTime>, // local time, time at 60-70 cm min soil water contain
+    pub h_vmc080: Option<FLOAT>,       // %,  70-80  cm soil water contain
+    pub h_vmc080x: Option<FLOAT>,      // %,  70-80  cm max soil water contain
+    pub h_vmc080xt: Option<NaiveTime>, // local time, time at 70-80 cm max soil water contain
+    pub h_vmc080n: Option<FLOAT>,      // %,  70-80  cm min soil water contain
+    pub h_vmc080nt: Option<NaiveTime>, // local time, time at 70-80 cm min soil water contain
+    pub h_vmc090: Option<FLOAT>,       // %,  80-90  cm soil water contain
+    pub h_vmc090x: Option<FLOAT>,      // %,  80-90  cm max soil water contain
+    pub h_vmc090xt: Option<NaiveTime>, // local time, time at 80-90 cm max soil water contain
+    pub h_vmc090n: Option<FLOAT>,      // %,  80-90  cm min soil water contain
+    pub h_vmc090nt: Option<NaiveTime>, // local time, time at 80-90 cm min soil water contain
+    pub h_vmc100: Option<FLOAT>,       // %,  90-100 cm soil water contain
+    pub h_vmc100x: Option<FLOAT>,      // %,  90-100 cm max soil water contain
+    pub h_vmc100xt: Option<NaiveTime>, // local time, time at 90-100 cm max soil water contain
+    pub h_vmc100n: Option<FLOAT>,      // %,  90-100 cm min soil water contain
+    pub h_vmc100nt: Option<NaiveTime>, // local time, time at 90-100 cm min soil water contain
+    pub h_vmc110: Option<FLOAT>,       // %, 100-110 cm soil water contain
+    pub h_vmc110x: Option<FLOAT>,      // %, 100-110  cm max soil water contain
+    pub h_vmc110xt: Option<NaiveTime>, // local time, time at 100-110 cm max soil water contain
+    pub h_vmc110n: Option<FLOAT>,      // %, 100-110  cm min soil water contain
+    pub h_vmc110nt: Option<NaiveTime>, // local time, time at 100-110 cm min soil water contain
+    pub h_vmc120: Option<FLOAT>,       // %, 110-120 cm soil water contain
+    pub h_vmc120x: Option<FLOAT>,      // %, 110-120 cm max soil water contain
+    pub h_vmc120xt: Option<NaiveTime>, // local time, time at 110-120 cm max soil water contain
+    pub h_vmc120n: Option<FLOAT>,      // %, 110-120 cm min soil water contain
+    pub h_vmc120nt: Option<NaiveTime>, // local time, time at 110-120 cm min soil water contain
 }
 
 impl CWBSoilHourData {
@@ -832,71 +881,72 @@ impl CWBSoilHourData {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CWBSoilDayData {
-    stationid: String,
-    dkind: String,
-    dtime: NaiveDateTime,  // local time
-    d_vmc010: FLOAT,       // %,   0-10  cm soil water contain
-    d_vmc010x: FLOAT,      // %,   0-10  cm max soil water contain
-    d_vmc010xt: NaiveTime, // local time, time at 0-10 cm max soil water contain
-    d_vmc010n: FLOAT,      // %,   0-10  cm min soil water contain
-    d_vmc010nt: NaiveTime, // local time, time at 0-10 cm min soil water contain
-    d_vmc020: FLOAT,       // %,  10-20  cm soil water contain
-    d_vmc020x: FLOAT,      // %,  10-20  cm max soil water contain
-    d_vmc020xt: NaiveTime, // local time, time at 10-20 cm max soil water contain
-    d_vmc020n: FLOAT,      // %,  10-20  cm min soil water contain
-    d_vmc020nt: NaiveTime, // local time, time at 10-20 cm min soil water contain
-    d_vmc030: FLOAT,       // %,  20-30  cm soil water contain
-    d_vmc030x: FLOAT,      // %,  20-30  cm max soil water contain
-    d_vmc030xt: NaiveTime, // local time, time at 20-30 cm max soil water contain
-    d_vmc030n: FLOAT,      // %,  20-30  cm min soil water contain
-    d_vmc030nt: NaiveTime, // local time, time at 20-30 cm min soil water contain
-    d_vmc040: FLOAT,       // %,  30-40  cm soil water contain
-    d_vmc040x: FLOAT,      // %,  30-40  cm max soil water contain
-    d_vmc040xt: NaiveTime, // local time, time at 30-40 cm max soil water contain
-    d_vmc040n: FLOAT,      // %,  30-40  cm min soil water contain
-    d_vmc040nt: NaiveTime, // local time, time at 30-40 cm min soil water contain
-    d_vmc050: FLOAT,       // %,  40-50  cm soil water contain
-    d_vmc050x: FLOAT,      // %,  40-50  cm max soil water contain
-    d_vmc050xt: NaiveTime, // local time, time at 40-50 cm max soil water contain
-    d_vmc050n: FLOAT,      // %,  40-50  cm min soil water contain
-    d_vmc050nt: NaiveTime, // local time, time at 40-50 cm min soil water contain
-    d_vmc060: FLOAT,       // %,  50-60  cm soil water contain
-    d_vmc060x: FLOAT,      // %,  50-60  cm max soil water contain
-    d_vmc060xt: NaiveTime, // local time, time at 50-60 cm max soil water contain
-    d_vmc060n: FLOAT,      // %,  50-60  cm min soil water contain
-    d_vmc060nt: NaiveTime, // local time, time at 50-60 cm min soil water contain
-    d_vmc070: FLOAT,       // %,  60-70  cm soil water contain
-    d_vmc070x: FLOAT,      // %,  60-70  cm max soil water contain
-    d_vmc070xt: NaiveTime, // local time, time at 60-70 cm max soil water contain
-    d_vmc070n: FLOAT,      // %,  60-70  cm min soil water contain
-    d_vmc070nt: NaiveTime, // local time, time at 60-70 cm min soil water contain
-    d_vmc080: FLOAT,       // %,  70-80  cm soil water contain
-    d_vmc080x: FLOAT,      // %,  70-80  cm max soil water contain
-    d_vmc080xt: NaiveTime, // local time, time at 70-80 cm max soil water contain
-    d_vmc080n: FLOAT,      // %,  70-80  cm min soil water contain
-    d_vmc080nt: NaiveTime, // local time, time at 70-80 cm min soil water contain
-    d_vmc090: FLOAT,       // %,  80-90  cm soil water contain
-    d_vmc090x: FLOAT,      // %,  80-90  cm max soil water contain
-    d_vmc090xt: NaiveTime, // local time, time at 80-90 cm max soil water contain
-    d_vmc090n: FLOAT,      // %,  80-90  cm min soil water contain
-    d_vmc090nt: NaiveTime, // local time, time at 80-90 cm min soil water contain
-    d_vmc100: FLOAT,       // %,  90-100 cm soil water contain
-    d_vmc100x: FLOAT,      // %,  90-100 cm max soil water contain
-    d_vmc100xt: NaiveTime, // local time, time at 90-100 cm max soil water contain
-    d_vmc100n: FLOAT,      // %,  90-100 cm min soil water contain
-    d_vmc100nt: NaiveTime, // local time, time at 90-100 cm min soil water contain
-    d_vmc110: FLOAT,       // %, 100-110 cm soil water contain
-    d_vmc110x: FLOAT,      // %, 100-110  cm max soil water contain
-    d_vmc110xt: NaiveTime, // local time, time at 100-110 cm max soil water contain
-    d_vmc110n: FLOAT,      // %, 100-110  cm min soil water contain
-    d_vmc110nt: NaiveTime, // local time, time at 100-110 cm min soil water contain
-    d_vmc120: FLOAT,       // %, 110-120 cm soil water contain
-    d_vmc120x: FLOAT,      // %, 110-120 cm max soil water contain
-    d_vmc120xt: NaiveTime, // local time, time at 110-120 cm max soil water contain
-    d_vmc120n: FLOAT,      // %, 110-120 cm min soil water contain
-    d_vmc120nt: NaiveTime, // local time, time at 110-120 cm min soil water contain
+    pub stationid: String,
+    pub dkind: String,
+    #[serde(with = "datetime_format")]
+    pub dtime: NaiveDateTime, // local time
+    pub d_vmc010: Option<FLOAT>,       // %,   0-10  cm soil water contain
+    pub d_vmc010x: Option<FLOAT>,      // %,   0-10  cm max soil water contain
+    pub d_vmc010xt: Option<NaiveTime>, // local time, time at 0-10 cm max soil water contain
+    pub d_vmc010n: Option<FLOAT>,      // %,   0-10  cm min soil water contain
+    pub d_vmc010nt: Option<NaiveTime>, // local time, time at 0-10 cm min soil water contain
+    pub d_vmc020: Option<FLOAT>,       // %,  10-20  cm soil water contain
+    pub d_vmc020x: Option<FLOAT>,      // %,  10-20  cm max soil water contain
+    pub d_vmc020xt: Option<NaiveTime>, // local time, time at 10-20 cm max soil water contain
+    pub d_vmc020n: Option<FLOAT>,      // %,  10-20  cm min soil water contain
+    pub d_vmc020nt: Option<NaiveTime>, // local time, time at 10-20 cm min soil water contain
+    pub d_vmc030: Option<FLOAT>,       // %,  20-30  cm soil water contain
+    pub d_vmc030x: Option<FLOAT>,      // %,  20-30  cm max soil water contain
+    pub d_vmc030xt: Option<NaiveTime>, // local time, time at 20-30 cm max soil water contain
+    pub d_vmc030n: Option<FLOAT>,      // %,  20-30  cm min soil water contain
+    pub d_vmc030nt: Option<NaiveTime>, // local time, time at 20-30 cm min soil water contain
+    pub d_vmc040: Option<FLOAT>,       // %,  30-40  cm soil water contain
+    pub d_vmc040x: Option<FLOAT>,      // %,  30-40  cm max soil water contain
+    pub d_vmc040xt: Option<NaiveTime>, // local time, time at 30-40 cm max soil water contain
+    pub d_vmc040n: Option<FLOAT>,      // %,  30-40  cm min soil water contain
+    pub d_vmc040nt: Option<NaiveTime>, // local time, time at 30-40 cm min soil water contain
+    pub d_vmc050: Option<FLOAT>,       // %,  40-50  cm soil water contain
+    pub d_vmc050x: Option<FLOAT>,      // %,  40-50  cm max soil water contain
+    pub d_vmc050xt: Option<NaiveTime>, // local time, time at 40-50 cm max soil water contain
+    pub d_vmc050n: Option<FLOAT>,      // %,  40-50  cm min soil water contain
+    pub d_vmc050nt: Option<NaiveTime>, // local time, time at 40-50 cm min soil water contain
+    pub d_vmc060: Option<FLOAT>,       // %,  50-60  cm soil water contain
+    pub d_vmc060x: Option<FLOAT>,      // %,  50-60  cm max soil water contain
+    pub d_vmc060xt: Option<NaiveTime>, // local time, time at 50-60 cm max soil water contain
+    pub d_vmc060n: Option<FLOAT>,      // %,  50-60  cm min soil water contain
+    pub d_vmc060nt: Option<NaiveTime>, // local time, time at 50-60 cm min soil water contain
+    pub d_vmc070: Option<FLOAT>,       // %,  60-70  cm soil water contain
+    pub d_vmc070x: Option<FLOAT>,      // %,  60-70  cm max soil water contain
+    pub d_vmc070xt: Option<NaiveTime>, // local time, time at 60-70 cm max soil water contain
+    pub d_vmc070n: Option<FLOAT>,      // %,  60-70  cm min soil water contain
+    pub d_vmc070nt: Option<NaiveTime>, // local time, time at 60-70 cm min soil water contain
+    pub d_vmc080: Option<FLOAT>,       // %,  70-80  cm soil water contain
+    pub d_vmc080x: Option<FLOAT>,      // %,  70-80  cm max soil water contain
+    pub d_vmc080xt: Option<NaiveTime>, // local time, time at 70-80 cm max soil water contain
+    pub d_vmc080n: Option<FLOAT>,      // %,  70-80  cm min soil water contain
+    pub d_vmc080nt: Option<NaiveTime>, // local time, time at 70-80 cm min soil water contain
+    pub d_vmc090: Option<FLOAT>,       // %,  80-90  cm soil water contain
+    pub d_vmc090x: Option<FLOAT>,      // %,  80-90  cm max soil water contain
+    pub d_vmc090xt: Option<NaiveTime>, // local time, time at 80-90 cm max soil water contain
+    pub d_vmc090n: Option<FLOAT>,      // %,  80-90  cm min soil water contain
+    pub d_vmc090nt: Option<NaiveTime>, // local time, time at 80-90 cm min soil water contain
+    pub d_vmc100: Option<FLOAT>,       // %,  90-100 cm soil water contain
+    pub d_vmc100x: Option<FLOAT>,      // %,  90-100 cm max soil water contain
+    pub d_vmc100xt: Option<NaiveTime>, // local time, time at 90-100 cm max soil water contain
+    pub d_vmc100n: Option<FLOAT>,      // %,  90-100 cm min soil water contain
+    pub d_vmc100nt: Option<NaiveTime>, // local time, time at 90-100 cm min soil water contain
+    pub d_vmc110: Option<FLOAT>,       // %, 100-110 cm soil water contain
+    pub d_vmc110x: Option<FLOAT>,      // %, 100-110  cm max soil water contain
+    pub d_vmc110xt: Option<NaiveTime>, // local time, time at 100-110 cm max soil water contain
+    pub d_vmc110n: Option<FLOAT>,      // %, 100-110  cm min soil water contain
+    pub d_vmc110nt: Option<NaiveTime>, // local time, time at 100-110 cm min soil water contain
+    pub d_vmc120: Option<FLOAT>,       // %, 110-120 cm soil water contain
+    pub d_vmc120x: Option<FLOAT>,      // %, 110-120 cm max soil water contain
+    pub d_vmc120xt: Option<NaiveTime>, // local time, time at 110-120 cm max soil water contain
+    pub d_vmc120n: Option<FLOAT>,      // %, 110-120 cm min soil water contain
+    pub d_vmc120nt: Option<NaiveTime>, // local time, time at 110-120 cm min soil water contain
 }
 
 impl CWBSoilDayData {
@@ -988,6 +1038,87 @@ impl CWBSoilDayData {
     }
 }
 
+// validated CWB frame, serialized with the field names and `kind`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CwbRecord {
+    Min(CWBMinData),
+    Hour(CWBHourData),
+    Day(CWBDayData),
+    SoilMin(CWBSoilMinData),
+    SoilHour(CWBSoilHourData),
+    SoilDay(CWBSoilDayData),
+}
+
+impl CwbRecord {
+    pub fn parse_from_str(data: &str) -> Result<Self, Box<dyn Error + 'static>> {
+        match get_dkind(data).as_deref() {
+            Some("MN") => Ok(CwbRecord::Min(CWBMinData::parse_from_str(data)?)),
+            Some("HR") | Some("HH") => Ok(CwbRecord::Hour(CWBHourData::parse_from_str(data)?)),
+            Some("DY") | Some("DD") => Ok(CwbRecord::Day(CWBDayData::parse_from_str(data)?)),
+            Some("SM") => Ok(CwbRecord::SoilMin(CWBSoilMinData::parse_from_str(data)?)),
+            Some("SH") => Ok(CwbRecord::SoilHour(CWBSoilHourData::parse_from_str(data)?)),
+            Some("SD") => Ok(CwbRecord::SoilDay(CWBSoilDayData::parse_from_str(data)?)),
+            _ => Err(String::from("Invalid").into()),
+        }
+    }
+
+    pub fn dtime(&self) -> NaiveDateTime {
+        match self {
+            CwbRecord::Min(val) => val.dtime,
+            CwbRecord::Hour(val) => val.dtime,
+            CwbRecord::Day(val) => val.dtime,
+            CwbRecord::SoilMin(val) => val.dtime,
+            CwbRecord::SoilHour(val) => val.dtime,
+            CwbRecord::SoilDay(val) => val.dtime,
+        }
+    }
+
+    // field name -> value, `kind` is not included
+    pub fn to_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        let Ok(serde_json::Value::Object(mut result)) = serde_json::to_value(self) else {
+            return serde_json::Map::new();
+        };
+        result.remove("kind");
+        result
+    }
+
+    // set the fields matching their sentinels (rust name -> sentinels) to `None`
+    pub fn apply_missing(&mut self, sentinels: &HashMap<&str, &[String]>) {
+        let Ok(serde_json::Value::Object(mut fields)) = serde_json::to_value(&*self) else {
+            return;
+        };
+
+        let mut changed = false;
+        for (name, value) in fields.iter_mut() {
+            let Some(sentinels) = sentinels.get(name.as_str()) else {
+                continue;
+            };
+            let text = match value {
+                serde_json::Value::String(val) => val.clone(),
+                serde_json::Value::Number(val) => val.to_string(),
+                _ => continue,
+            };
+            if is_missing_value(&text, sentinels) {
+                *value = serde_json::Value::Null;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+
+        match serde_json::from_value(serde_json::Value::Object(fields)) {
+            Ok(record) => *self = record,
+            Err(e) => log::warn!("Apply missing values failed: {e}"),
+        }
+    }
+
+    pub fn missing_count(&self) -> usize {
+        self.to_json().values().filter(|val| val.is_null()).count()
+    }
+}
+
 pub type CWBCodecConfig = CodecConfigBase;
 
 #[derive(Debug, Deserialize)]
@@ -1024,9 +1155,16 @@ impl CWBCodecConfig {
                 if Some(true) == mem.raw_save {
                     tableinfo.push_str(", rawdata TEXT");
                 }
+                if mem.sitest.is_some() {
+                    for name in HEALTH_COLUMNS.iter() {
+                        tableinfo.push_str(&format!(", {name} INTEGER"));
+                    }
+                }
                 break;
             }
         }
+        tableinfo.push_str(", missing_count INTEGER");
+        tableinfo.push_str(", header_id INTEGER");
         tableinfo.push_str(", flag_uploaded BOOLEAN DEFAULT FALSE");
 
         if find {
@@ -1053,6 +1191,10 @@ impl CWBCodecConfig {
         }
         None
     }
+
+    pub fn get_data_config_by_name(&self, name: &str) -> Option<&CodecConfigMetadata> {
+        self.metadatas.iter().find(|mem| mem.name == name)
+    }
 }
 
 #[cfg(test)]
@@ -1069,6 +1211,53 @@ mod test {
         println!("{:?}", time);
     }
 
+    #[test]
+    fn parse_record() {
+        let frame = "\u{2},466920,SM,202501091555,\
+            1.5,2,3,4,5,6,7,8,9,10,11,12.5,0000,\u{3}";
+        let record = CwbRecord::parse_from_str(frame).unwrap();
+        let CwbRecord::SoilMin(data) = &record else {
+            panic!("Unexpected record: {record:?}");
+        };
+        assert_eq!(data.stationid, "466920");
+        assert_eq!(data.dd_vmc120, Some(12.5));
+
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value["kind"], "soil_min");
+        assert_eq!(value["dtime"], "2025-01-09 15:55:00");
+        let ret: CwbRecord = serde_json::from_value(value).unwrap();
+        assert_eq!(ret.dtime(), record.dtime());
+        assert!(!record.to_json().contains_key("kind"));
+
+        // field count is checked
+        assert!(CwbRecord::parse_from_str("\u{2},466920,MN,202501091555,\u{3}").is_err());
+    }
+
+    #[test]
+    fn missing_values() {
+        let frame = "\u{2},466920,SM,202501091555,\
+            ,/////,-9999,4,5,6,7,8,9,10,11,12.5,,\u{3}";
+        let mut record = CwbRecord::parse_from_str(frame).unwrap();
+        assert_eq!(record.missing_count(), 3);
+
+        let sentinels = vec!["-9999.0".to_string()];
+        let missing = HashMap::from([("dd_vmc030", sentinels.as_slice())]);
+        record.apply_missing(&missing);
+        assert_eq!(record.missing_count(), 4);
+        let CwbRecord::SoilMin(data) = &record else {
+            panic!("Unexpected record: {record:?}");
+        };
+        assert_eq!(data.dd_vmc010, None);
+        assert_eq!(data.dd_vmc030, None);
+        assert_eq!(data.dd_vmc040, Some(4.0));
+        assert_eq!(data.dd_sitest, None);
+
+        // non-numeric value is still rejected
+        let frame = "\u{2},466920,SM,202501091555,\
+            x,2,3,4,5,6,7,8,9,10,11,12.5,0000,\u{3}";
+        assert!(CwbRecord::parse_from_str(frame).is_err());
+    }
+
     #[test]
     fn test_read_config() {
         let path = "config.json.ignore";
@@ -0,0 +1,117 @@
+// CWB station status code (`dd_sitest`), one character per sensor in the configured order
+// '0' is normal, other characters are fault, sensors not in the code are unknown
+pub const SENSORS: [&str; 6] = [
+    "pressure",
+    "temperature",
+    "wind",
+    "rain",
+    "radiation",
+    "soil",
+];
+// sqlite3 columns of `SENSORS`, 1 is fault, 0 is normal and NULL is unknown
+pub const HEALTH_COLUMNS: [&str; 6] = [
+    "health_pressure",
+    "health_temperature",
+    "health_wind",
+    "health_rain",
+    "health_radiation",
+    "health_soil",
+];
+
+// fault flag of each sensor in `SENSORS`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SensorHealth {
+    pub flags: [Option<bool>; 6],
+}
+
+#[allow(unused)]
+impl SensorHealth {
+    pub fn decode(code: &str, sensors: &[String]) -> Self {
+        let mut result = SensorHealth::default();
+        for (flag, sensor) in code.trim().chars().zip(sensors.iter()) {
+            let Some(idx) = SENSORS.iter().position(|val| val == sensor) else {
+                continue;
+            };
+            result.flags[idx] = Some(flag != '0');
+        }
+        result
+    }
+
+    pub fn is_fault(&self, sensor: &str) -> Option<bool> {
+        let idx = SENSORS.iter().position(|&val| val == sensor)?;
+        self.flags[idx]
+    }
+
+    // sensors which are fault now but not in `prev`
+    pub fn new_faults(&self, prev: Option<&SensorHealth>) -> Vec<&'static str> {
+        SENSORS
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| self.flags[idx] == Some(true))
+            .filter(|&(idx, _)| prev.is_none_or(|val| val.flags[idx] != Some(true)))
+            .map(|(_, &sensor)| sensor)
+            .collect()
+    }
+
+    // sensors which were fault in `prev` and are normal now
+    pub fn recovered(&self, prev: Option<&SensorHealth>) -> Vec<&'static str> {
+        let Some(prev) = prev else {
+            return Vec::new();
+        };
+        SENSORS
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| prev.flags[idx] == Some(true) && self.flags[idx] == Some(false))
+            .map(|(_, &sensor)| sensor)
+            .collect()
+    }
+
+    pub fn to_sqlite3_values(&self) -> Vec<sqlite::Value> {
+        self.flags
+            .iter()
+            .map(|flag| match flag {
+                Some(val) => sqlite::Value::Integer(*val as i64),
+                None => sqlite::Value::Null,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_status() {
+        let sensors = SENSORS.map(|val| val.to_string());
+        let health = SensorHealth::decode("0100", &sensors);
+        assert_eq!(health.is_fault("pressure"), Some(false));
+        assert_eq!(health.is_fault("temperature"), Some(true));
+        assert_eq!(health.is_fault("radiation"), None);
+        assert_eq!(
+            health.to_sqlite3_values()[..3],
+            [
+                sqlite::Value::Integer(0),
+                sqlite::Value::Integer(1),
+                sqlite::Value::Integer(0)
+            ]
+        );
+
+        // soil record has its own order
+        let health = SensorHealth::decode("1", &["soil".to_string()]);
+        assert_eq!(health.is_fault("soil"), Some(true));
+        assert_eq!(health.is_fault("pressure"), None);
+    }
+
+    #[test]
+    fn transitions() {
+        let sensors = SENSORS.map(|val| val.to_string());
+        let normal = SensorHealth::decode("0000", &sensors);
+        let fault = SensorHealth::decode("0010", &sensors);
+        assert_eq!(fault.new_faults(Some(&normal)), vec!["wind"]);
+        assert_eq!(fault.new_faults(None), vec!["wind"]);
+        assert!(fault.new_faults(Some(&fault)).is_empty());
+        assert_eq!(normal.recovered(Some(&fault)), vec!["wind"]);
+        assert!(normal.recovered(None).is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+// LoggerNet TOA5 file header
+// 1. "TOA5",station,logger model,logger serial,os version,program,program signature,table
+// 2. field names
+// 3. units
+// 4. processing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toa5Header {
+    pub station: String,
+    pub logger_model: String,
+    pub logger_serial: String,
+    pub os_version: String,
+    pub program: String,
+    pub program_signature: String,
+    pub table: String,
+    pub fields: Vec<String>,
+    pub units: Vec<String>,
+    pub processing: Vec<String>,
+}
+
+fn split_line(line: &str) -> Vec<String> {
+    line.trim_end()
+        .split(',')
+        .map(|val| val.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+impl Toa5Header {
+    pub fn parse(header: &[String]) -> Option<Self> {
+        let [info, fields, units, processing, ..] = header else {
+            return None;
+        };
+
+        let info = split_line(info);
+        if info.first().map(|val| val.as_str()) != Some("TOA5") {
+            return None;
+        }
+        let get = |idx: usize| info.get(idx).cloned().unwrap_or_default();
+
+        Some(Toa5Header {
+            station: get(1),
+            logger_model: get(2),
+            logger_serial: get(3),
+            os_version: get(4),
+            program: get(5),
+            program_signature: get(6),
+            table: get(7),
+            fields: split_line(fields),
+            units: split_line(units),
+            processing: split_line(processing),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_header() {
+        let header = [
+            "\"TOA5\",\"NAHO\",\"CR1000X\",\"12345\",\"CR1000X.Std.05\",\
+             \"CPU:naho.CR1X\",\"4321\",\"Table1\"\r\n",
+            "\"TIMESTAMP\",\"RECORD\",\"AirT_Avg\"\r\n",
+            "\"TS\",\"RN\",\"Deg C\"\r\n",
+            "\"\",\"\",\"Avg\"\r\n",
+        ]
+        .map(|val| val.to_string());
+
+        let ret = Toa5Header::parse(&header).unwrap();
+        assert_eq!(ret.station, "NAHO");
+        assert_eq!(ret.logger_model, "CR1000X");
+        assert_eq!(ret.logger_serial, "12345");
+        assert_eq!(ret.program, "CPU:naho.CR1X");
+        assert_eq!(ret.table, "Table1");
+        assert_eq!(ret.fields, vec!["TIMESTAMP", "RECORD", "AirT_Avg"]);
+        assert_eq!(ret.units[2], "Deg C");
+        assert_eq!(ret.processing[2], "Avg");
+
+        assert!(Toa5Header::parse(&header[1..]).is_none());
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::TimeDelta;
+
+use super::Level1Row;
+use crate::component::codec::{CodecConfigDataTypeQc, CodecConfigMetadata};
+
+// flags of the automated checks, the largest flag of a value is kept
+pub const FLAG_RANGE: usize = 21; // out of `min` ~ `max`
+pub const FLAG_STEP: usize = 22; // change from the previous row is over `step`
+pub const FLAG_PERSISTENCE: usize = 23; // same value for `persistence` rows or more
+pub const FLAG_CONSISTENCY: usize = 24; // value is over the `upper` column
+
+fn get_number(row: &Level1Row, idx: usize) -> Option<f64> {
+    row.values[idx].as_deref()?.parse::<f64>().ok()
+}
+
+fn set_flag(row: &mut Level1Row, idx: usize, flag: usize) {
+    row.flags[idx] = row.flags[idx].max(flag);
+}
+
+fn check_range(rows: &mut [Level1Row], idx: usize, cfg: &CodecConfigDataTypeQc) {
+    for row in rows.iter_mut() {
+        let Some(val) = get_number(row, idx) else {
+            continue;
+        };
+        if cfg.min.is_some_and(|min| val < min) || cfg.max.is_some_and(|max| val > max) {
+            set_flag(row, idx, FLAG_RANGE);
+        }
+    }
+}
+
+fn check_step(rows: &mut [Level1Row], idx: usize, cfg: &CodecConfigDataTypeQc) {
+    let Some(step) = cfg.step else {
+        return;
+    };
+    let gap = TimeDelta::seconds(cfg.get_step_gap() as i64);
+    for pos in 1..rows.len() {
+        if rows[pos].time - rows[pos - 1].time > gap {
+            continue;
+        }
+        let (Some(prev), Some(val)) =
+            (get_number(&rows[pos - 1], idx), get_number(&rows[pos], idx))
+        else {
+            continue;
+        };
+        if (val - prev).abs() > step {
+            set_flag(&mut rows[pos], idx, FLAG_STEP);
+        }
+    }
+}
+
+fn check_persistence(rows: &mut [Level1Row], idx: usize, cfg: &CodecConfigDataTypeQc) {
+    let Some(persistence) = cfg.persistence else {
+        return;
+    };
+    let mut start = 0;
+    while start < rows.len() {
+        let Some(val) = get_number(&rows[start], idx) else {
+            start += 1;
+            continue;
+        };
+        let mut end = start + 1;
+        while end < rows.len() && get_number(&rows[end], idx) == Some(val) {
+            end += 1;
+        }
+        if end - start >= persistence {
+            for row in rows[start..end].iter_mut() {
+                set_flag(row, idx, FLAG_PERSISTENCE);
+            }
+        }
+        start = end;
+    }
+}
+
+fn check_consistency(
+    rows: &mut [Level1Row],
+    idx: usize,
+    cfg: &CodecConfigDataTypeQc,
+    dconfig: &CodecConfigMetadata,
+) {
+    let Some(upper) = cfg.upper.as_deref() else {
+        return;
+    };
+    let Some(upper_idx) = dconfig
+        .formation
+        .iter()
+        .position(|val| val.sqlite3.name == upper)
+    else {
+        log::error!("QC upper column not found: {}.{upper}", dconfig.name);
+        return;
+    };
+    for row in rows.iter_mut() {
+        let (Some(val), Some(limit)) = (get_number(row, idx), get_number(row, upper_idx)) else {
+            continue;
+        };
+        if val > limit {
+            set_flag(row, idx, FLAG_CONSISTENCY);
+            set_flag(row, upper_idx, FLAG_CONSISTENCY);
+        }
+    }
+}
+
+// checks of `formation.qc`, rows are sorted by time
+pub fn apply_checks(rows: &mut [Level1Row], dconfig: &CodecConfigMetadata) {
+    for (idx, dtype) in dconfig.formation.iter().enumerate() {
+        let Some(cfg) = dtype.qc.as_ref() else {
+            continue;
+        };
+        check_range(rows, idx, cfg);
+        check_step(rows, idx, cfg);
+        check_persistence(rows, idx, cfg);
+        check_consistency(rows, idx, cfg, dconfig);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::component::{qc_level1::FLAG_GOOD, DTAETIME_FMT};
+
+    fn rows(values: &[(&str, Option<&str>, Option<&str>)]) -> Vec<Level1Row> {
+        values
+            .iter()
+            .map(|(time, t, td)| Level1Row {
+                time: NaiveDateTime::parse_from_str(time, DTAETIME_FMT).unwrap(),
+                values: vec![
+                    Some(time.to_string()),
+                    t.map(|val| val.to_string()),
+                    td.map(|val| val.to_string()),
+                ],
+                flags: vec![FLAG_GOOD; 3],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn automated_checks() {
+        let dconfig: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["MN"],
+            "formation": [
+                {
+                    "spec": {"name": "dtime", "description": "", "dtype": "text"},
+                    "rust": {"name": "dtime", "dtype": "text", "major_datetime": true},
+                    "sqlite3": {"name": "dtime", "dtype": "TEXT"}
+                },
+                {
+                    "spec": {"name": "t", "description": "", "dtype": "float"},
+                    "rust": {"name": "dd_t", "dtype": "float"},
+                    "sqlite3": {"name": "dd_t", "dtype": "REAL"},
+                    "qc": {"min": -20.0, "max": 45.0, "step": 3.0, "persistence": 3}
+                },
+                {
+                    "spec": {"name": "td", "description": "", "dtype": "float"},
+                    "rust": {"name": "dd_td", "dtype": "float"},
+                    "sqlite3": {"name": "dd_td", "dtype": "REAL"},
+                    "qc": {"upper": "dd_t"}
+                }
+            ]
+        }))
+        .unwrap();
+
+        let mut data = rows(&[
+            ("2025-01-09 15:50:00", Some("20.0"), Some("15.0")),
+            ("2025-01-09 15:51:00", Some("25.0"), Some("15.0")),
+            ("2025-01-09 15:52:00", Some("50.0"), None),
+            // gap is over `step_gap`
+            ("2025-01-09 16:00:00", Some("10.0"), Some("12.0")),
+            ("2025-01-09 16:01:00", Some("10.0"), Some("9.0")),
+            ("2025-01-09 16:02:00", Some("10.0"), Some("9.0")),
+        ]);
+        apply_checks(&mut data, &dconfig);
+
+        let flags = data.iter().map(|row| row.flags[1]).collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            vec![
+                FLAG_GOOD,
+                FLAG_STEP,
+                FLAG_STEP.max(FLAG_RANGE),
+                FLAG_CONSISTENCY,
+                FLAG_PERSISTENCE,
+                FLAG_PERSISTENCE
+            ]
+        );
+        let flags = data.iter().map(|row| row.flags[2]).collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            vec![
+                FLAG_GOOD,
+                FLAG_GOOD,
+                FLAG_GOOD,
+                FLAG_CONSISTENCY,
+                FLAG_GOOD,
+                FLAG_GOOD
+            ]
+        );
+    }
+}
@@ -29,7 +29,7 @@ struct FilterRule {
 }
 
 #[derive(Debug, Default)]
-struct Filter {
+pub struct Filter {
     flags: FilterFlagTable,
     rules: FilterRuleTable,
 }
@@ -85,7 +85,10 @@ impl Filter {
             let mut buffer = String::new();
 
             // find header
-            while let Ok(_nums) = reader.read_line(&mut buffer) {
+            loop {
+                if reader.read_line(&mut buffer)? == 0 {
+                    return Err(format!("Filter header not found: {src:?}").into());
+                }
                 if buffer.find(FILTER_HEADER_ST).is_some()
                     && buffer.find(FILTER_HEADER_ET).is_some()
                     && buffer.find(FILTER_HEADER_FLAG).is_some()
@@ -139,7 +142,7 @@ impl Filter {
                 let rule = FilterRule {
                     starttime: st,
                     endtime: et,
-                    target: buf.to_string(),
+                    target: buf.trim().to_string(),
                 };
                 let entry = self.rules.entry(cfg.0.to_string()).or_insert(Vec::new());
                 entry.push(rule);
@@ -148,6 +151,34 @@ impl Filter {
         }
         Ok(())
     }
+
+    // variable -> flag at `time` of `level`, the largest flag is used if rules overlap
+    pub fn get_variable_flags(&self, level: usize, time: &NaiveDateTime) -> HashMap<String, usize> {
+        let mut result = HashMap::new();
+        let tag = format!("{CFG_LEVEL}{level}");
+        let Some(rules) = self.rules.get(&tag) else {
+            return result;
+        };
+
+        for rule in rules.iter() {
+            if time < &rule.starttime || time > &rule.endtime {
+                continue;
+            }
+            let Ok(flag) = rule.target.parse::<usize>() else {
+                log::error!("Invalid flag: {:?}", rule.target);
+                continue;
+            };
+            let Some(variables) = self.get_flags(level, flag) else {
+                log::error!("Flag not found in {tag}: {flag}");
+                continue;
+            };
+            for name in variables.iter().filter_map(|val| val.as_str()) {
+                let entry = result.entry(name.to_string()).or_insert(flag);
+                *entry = (*entry).max(flag);
+            }
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -164,4 +195,35 @@ mod test {
         println!("{:?}", cfg.get_flags(1, 41));
         println!("{:?}", cfg.get_flags(1, 43));
     }
+
+    #[test]
+    fn variable_flags() {
+        let root = std::env::temp_dir().join("naho_filter_test");
+        std::fs::create_dir_all(&root).unwrap();
+        let source = root.join("filter.csv");
+        std::fs::write(
+            &source,
+            "Manual flags\r\n\
+            Start time(LT),End time(LT),Flag\r\n\
+            \"2025-01-09 00:00:00\",\"2025-01-09 12:00:00\",11\r\n\
+            \"2025-01-09 06:00:00\",\"2025-01-09 07:00:00\",41\r\n",
+        )
+        .unwrap();
+        let path = root.join("filter.json");
+        let flags = json!({
+            "Level1": {"source": source, "11": ["temp", "rh"], "41": ["temp"]}
+        });
+        std::fs::write(&path, flags.to_string()).unwrap();
+
+        let mut filter = Filter::new();
+        filter.load(&path).unwrap();
+        let time = |val| NaiveDateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S").unwrap();
+        let ret = filter.get_variable_flags(1, &time("2025-01-09 06:30:00"));
+        assert_eq!(ret["temp"], 41);
+        assert_eq!(ret["rh"], 11);
+        assert!(filter
+            .get_variable_flags(1, &time("2025-01-09 13:00:00"))
+            .is_empty());
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
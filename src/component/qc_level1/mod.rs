@@ -1,7 +1,29 @@
 #![allow(unused)]
-mod filter;
+pub mod checks;
+pub mod filter;
 
-use std::{error::Error, path::Path};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{create_dir_all, rename, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use filter::Filter;
+
+use super::{
+    codec::{CodecConfigBase, CodecConfigMetadata},
+    utils::files::{csv_escape, generate_db_filepath},
+    DTAETIME_FMT,
+};
+
+pub const LEVEL: usize = 1;
+// flag of a value which passed all checks
+pub const FLAG_GOOD: usize = 0;
+// column of the flag of `<name>` in l1_data
+const FLAG_SUFFIX: &str = "_flag";
 
 pub fn sqlite_get_columns<P>(path: P, table: &str) -> Result<Vec<String>, Box<dyn Error + 'static>>
 where
@@ -38,11 +60,198 @@ where
     Ok(())
 }
 
+// deduped row in order of `formation`
+#[derive(Debug, Clone)]
+pub struct Level1Row {
+    pub time: NaiveDateTime,
+    pub values: Vec<Option<String>>,
+    pub flags: Vec<usize>,
+}
+
+// deduped and sorted rows of `dconfig` in the database, columns not in the table are None
+fn read_rows<P>(
+    path: P,
+    dconfig: &CodecConfigMetadata,
+) -> Result<Vec<Level1Row>, Box<dyn Error + 'static>>
+where
+    P: AsRef<Path>,
+{
+    let Some((dt_idx, datetime_info)) = dconfig.get_datetime_info() else {
+        return Err(format!("major_datetime not found: {:?}", dconfig.name).into());
+    };
+
+    let mut result = Vec::new();
+    sqlite_dedup_and_sort_by(
+        path,
+        &dconfig.name,
+        &datetime_info.sqlite3.name,
+        &datetime_info.sqlite3.name,
+        |row| {
+            let row = row.iter().copied().collect::<HashMap<_, _>>();
+            let values = dconfig
+                .formation
+                .iter()
+                .map(|val| row.get(val.sqlite3.name.as_str()).copied().flatten())
+                .map(|val| val.map(|val| val.to_string()))
+                .collect::<Vec<_>>();
+            let time = values[dt_idx]
+                .as_deref()
+                .and_then(|val| NaiveDateTime::parse_from_str(val, DTAETIME_FMT).ok());
+            match time {
+                Some(time) => result.push(Level1Row {
+                    time,
+                    flags: vec![FLAG_GOOD; values.len()],
+                    values,
+                }),
+                None => log::error!("Invalid datetime: {:?} - {row:?}", dconfig.name),
+            }
+            true
+        },
+    )?;
+    Ok(result)
+}
+
+// manual time-window flags of `filter`, variables are `formation.sqlite3.name`
+fn apply_filter(rows: &mut [Level1Row], dconfig: &CodecConfigMetadata, filter: &Filter) {
+    for row in rows.iter_mut() {
+        let flags = filter.get_variable_flags(LEVEL, &row.time);
+        if flags.is_empty() {
+            continue;
+        }
+        for (idx, dtype) in dconfig.formation.iter().enumerate() {
+            if let Some(flag) = flags.get(&dtype.sqlite3.name) {
+                row.flags[idx] = row.flags[idx].max(*flag);
+            }
+        }
+    }
+}
+
+// csv of values and `<name>_flag` of each column except the major datetime
+fn write_rows<P>(
+    path: P,
+    rows: &[Level1Row],
+    dconfig: &CodecConfigMetadata,
+) -> Result<(), Box<dyn Error + 'static>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if let Some(root) = path.parent() {
+        create_dir_all(root)?;
+    }
+    let dt_idx = dconfig.get_datetime_info().map(|(idx, _)| idx);
+
+    // replaced at once, so a reader never sees a partial file
+    let tmp_path = path.with_extension("tmp");
+    let mut output = BufWriter::new(File::create(&tmp_path)?);
+    let mut header = dconfig
+        .formation
+        .iter()
+        .map(|val| val.sqlite3.name.clone())
+        .collect::<Vec<_>>();
+    for (idx, dtype) in dconfig.formation.iter().enumerate() {
+        if Some(idx) != dt_idx {
+            header.push(format!("{}{FLAG_SUFFIX}", dtype.sqlite3.name));
+        }
+    }
+    writeln!(output, "{}", header.join(","))?;
+
+    for row in rows.iter() {
+        let mut line = row
+            .values
+            .iter()
+            .map(|val| csv_escape(val.as_deref().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        for (idx, flag) in row.flags.iter().enumerate() {
+            if Some(idx) != dt_idx {
+                line.push(flag.to_string());
+            }
+        }
+        writeln!(output, "{}", line.join(","))?;
+    }
+    output.flush()?;
+    drop(output);
+    rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// level-1 qc of `dconfig` at `date`, return (l1_data file, rows)
+// None if the sqlite3 database of the date doesn't exist
+pub fn process_level1(
+    codec: &CodecConfigBase,
+    dconfig: &CodecConfigMetadata,
+    date: NaiveDate,
+    filter: &Filter,
+) -> Result<Option<(PathBuf, usize)>, Box<dyn Error + 'static>> {
+    let Some(cfg_sqlite3) = codec.sqlite3.as_ref() else {
+        return Err(format!("Unsupport sqlite3: {:?}", codec.tag).into());
+    };
+    let Some(cfg_l1_data) = codec.l1_data.as_ref() else {
+        return Err(format!("Unsupport l1_data: {:?}", codec.tag).into());
+    };
+
+    let mut opts = HashMap::new();
+    opts.insert(
+        "datetime".to_string(),
+        date.and_hms_opt(0, 0, 0)
+            .unwrap()
+            .format(DTAETIME_FMT)
+            .to_string(),
+    );
+    let Some(src) = generate_db_filepath(&codec.tag, cfg_sqlite3, dconfig, &opts) else {
+        return Err(format!("Generate database path failed: {:?}", dconfig.name).into());
+    };
+    if !src.exists() {
+        log::info!("Database not found: {src:?}");
+        return Ok(None);
+    }
+    let Some(dst) = generate_db_filepath(&codec.tag, cfg_l1_data, dconfig, &opts) else {
+        return Err(format!("Generate l1_data path failed: {:?}", dconfig.name).into());
+    };
+
+    let mut rows = read_rows(&src, dconfig)?;
+    apply_filter(&mut rows, dconfig, filter);
+    checks::apply_checks(&mut rows, dconfig);
+    write_rows(&dst, &rows, dconfig)?;
+    Ok(Some((dst, rows.len())))
+}
+
 #[cfg(test)]
 mod test {
     use crate::component::qc_level1::sqlite_get_columns;
 
-    use super::sqlite_dedup_and_sort_by;
+    use super::*;
+
+    #[test]
+    fn write_level1() {
+        let formation = |name: &str, unit: Option<&str>| {
+            serde_json::json!({
+                "spec": {"name": name, "description": "", "dtype": "text"},
+                "rust": {
+                    "name": name, "dtype": "text", "unit": unit, "major_datetime": unit.is_some()
+                },
+                "sqlite3": {"name": name, "dtype": "TEXT", "unit": unit}
+            })
+        };
+        let dconfig: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["MN"],
+            "formation": [formation("dtime", Some("%Y%m%d%H%M")), formation("temp", None)]
+        }))
+        .unwrap();
+        let time = NaiveDateTime::parse_from_str("2025-01-09 15:55:00", DTAETIME_FMT).unwrap();
+        let rows = vec![Level1Row {
+            time,
+            values: vec![Some("2025-01-09 15:55:00".to_string()), None],
+            flags: vec![FLAG_GOOD, 11],
+        }];
+
+        let path = std::env::temp_dir().join("naho_l1_test/test.csv");
+        write_rows(&path, &rows, &dconfig).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "dtime,temp,temp_flag\n2025-01-09 15:55:00,,11\n");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 
     #[test]
     fn case1() {
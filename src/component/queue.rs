@@ -0,0 +1,519 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::{create_dir_all, remove_file, rename, File, OpenOptions},
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::config::SinkConfig;
+
+use super::MsgPayload;
+
+pub const DEFAULT_CAPACITY: usize = 10000;
+pub const DEFAULT_SPOOL_DIRECTORY: &str = "data/spool";
+pub const DEFAULT_BLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+// block policy gives the message back after this, so the dispatcher can check the sink
+const BLOCK_TIMEOUT: Duration = Duration::from_millis(500);
+const DROP_REPORT_INTERVAL: usize = 1000;
+
+// what to do when the queue of a sink is full
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    #[default]
+    Block,
+    DropOldest,
+    Spool,
+}
+
+// overflowed messages as json lines, `<name>.spool` is appended and
+// renamed to `<name>.replay` when the sink catches up
+// the replayed byte offset is kept in `<name>.offset`, a restart continues from it
+struct Spool {
+    path: PathBuf,
+    replay_path: PathBuf,
+    offset_path: PathBuf,
+    writer: Option<File>,
+    spooled: usize,
+    reader: Option<BufReader<File>>,
+    offset: u64,
+}
+
+fn spool_path(directory: &Path, name: &str, extension: &str) -> PathBuf {
+    directory.join(format!("{name}.{extension}"))
+}
+
+// spool or replay file left by the previous run
+fn has_spool(directory: &Path, name: &str) -> bool {
+    ["spool", "replay"]
+        .iter()
+        .any(|val| spool_path(directory, name, val).exists())
+}
+
+fn load_offset(path: &Path) -> u64 {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|val| val.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+// replaced at once, the offset is never half written
+fn save_offset(path: &Path, offset: u64) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, offset.to_string())?;
+    rename(&tmp_path, path)
+}
+
+impl Spool {
+    // messages left by the previous run are replayed first
+    fn open<P>(directory: P, name: &str) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let directory = directory.as_ref();
+        create_dir_all(directory)?;
+        let path = spool_path(directory, name, "spool");
+        let replay_path = spool_path(directory, name, "replay");
+        let offset_path = spool_path(directory, name, "offset");
+
+        let spooled = match File::open(&path) {
+            Ok(file) => BufReader::new(file).lines().count(),
+            Err(_) => 0,
+        };
+        let mut offset = 0;
+        let reader = match File::open(&replay_path) {
+            Ok(mut file) => {
+                offset = load_offset(&offset_path);
+                file.seek(SeekFrom::Start(offset))?;
+                Some(BufReader::new(file))
+            }
+            Err(_) => None,
+        };
+
+        Ok(Spool {
+            path,
+            replay_path,
+            offset_path,
+            writer: None,
+            spooled,
+            reader,
+            offset,
+        })
+    }
+
+    // the replay file is finished or unreadable
+    fn finish_replay(&mut self) {
+        self.reader = None;
+        self.offset = 0;
+        for path in [&self.replay_path, &self.offset_path] {
+            if let Err(e) = remove_file(path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::error!("Remove spool failed: {e} - {path:?}");
+                }
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.reader.is_some() || self.spooled > 0
+    }
+
+    fn push(&mut self, msg: &MsgPayload) -> Result<(), Box<dyn Error + 'static>> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => self.writer.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            ),
+        };
+        let line = format!("{}\n", serde_json::to_string(msg)?);
+        writer.write_all(line.as_bytes())?;
+        self.spooled += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<MsgPayload> {
+        loop {
+            if let Some(reader) = self.reader.as_mut() {
+                let mut line = Vec::new();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => self.finish_replay(),
+                    Ok(num) => {
+                        // finished at the last line, new messages aren't held behind it
+                        if reader.fill_buf().is_ok_and(|val| val.is_empty()) {
+                            self.finish_replay();
+                        } else {
+                            self.offset += num as u64;
+                            if let Err(e) = save_offset(&self.offset_path, self.offset) {
+                                log::error!(
+                                    "Save spool offset failed: {e} - {:?}",
+                                    self.offset_path
+                                );
+                            }
+                        }
+                        let line = String::from_utf8_lossy(&line);
+                        match serde_json::from_str(&line) {
+                            Ok(msg) => return Some(msg),
+                            Err(e) => log::error!("Invalid spool line: {e} - {line:?}"),
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Read spool failed: {e} - {:?}", self.replay_path);
+                        self.finish_replay();
+                    }
+                }
+                continue;
+            }
+
+            if self.spooled == 0 {
+                return None;
+            }
+
+            // replay the spooled messages, new ones go to a fresh spool file
+            self.writer = None;
+            let ret =
+                rename(&self.path, &self.replay_path).and_then(|_| File::open(&self.replay_path));
+            self.spooled = 0;
+            match ret {
+                Ok(file) => self.reader = Some(BufReader::new(file)),
+                Err(e) => {
+                    log::error!("Replay spool failed: {e} - {:?}", self.path);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+struct QueueState {
+    buffer: VecDeque<Arc<MsgPayload>>,
+    spool: Option<Spool>,
+    closed: bool,
+    dropped: usize,
+}
+
+// bounded queue between the dispatcher and a sink, kept across sink restarts
+pub struct SinkQueue {
+    name: String,
+    capacity: usize,
+    policy: OverflowPolicy,
+    block_timeout: Duration,
+    // `Block` overflow is spooled here after `block_timeout`
+    spool_directory: PathBuf,
+    state: Mutex<QueueState>,
+    cond: Condvar,
+}
+
+impl SinkQueue {
+    pub fn new(
+        name: &str,
+        capacity: usize,
+        policy: OverflowPolicy,
+        spool_directory: &str,
+    ) -> Result<Self, Box<dyn Error + 'static>> {
+        if capacity == 0 {
+            return Err(format!("Invalid queue capacity: {capacity}").into());
+        }
+
+        let spool = match policy {
+            OverflowPolicy::Spool => Some(Spool::open(spool_directory, name)?),
+            OverflowPolicy::Block if has_spool(Path::new(spool_directory), name) => {
+                Some(Spool::open(spool_directory, name)?)
+            }
+            _ => None,
+        };
+        if let Some(spool) = spool.as_ref().filter(|val| val.is_active()) {
+            log::info!(target: "info", "Replay spool of {name}: {:?}", spool.path);
+        }
+
+        Ok(SinkQueue {
+            name: name.to_string(),
+            capacity,
+            policy,
+            block_timeout: DEFAULT_BLOCK_TIMEOUT,
+            spool_directory: PathBuf::from(spool_directory),
+            state: Mutex::new(QueueState {
+                buffer: VecDeque::new(),
+                spool,
+                closed: false,
+                dropped: 0,
+            }),
+            cond: Condvar::new(),
+        })
+    }
+
+    pub fn from_config(cfg: &SinkConfig) -> Result<Self, Box<dyn Error + 'static>> {
+        let queue = SinkQueue::new(
+            cfg.get_name(),
+            cfg.capacity.unwrap_or(DEFAULT_CAPACITY),
+            cfg.overflow.unwrap_or_default(),
+            cfg.spool_directory
+                .as_deref()
+                .unwrap_or(DEFAULT_SPOOL_DIRECTORY),
+        )?;
+        Ok(queue.with_block_timeout(cfg.get_block_timeout()))
+    }
+
+    pub fn with_block_timeout(mut self, timeout: Duration) -> Self {
+        self.block_timeout = timeout;
+        self
+    }
+
+    // total wait of a `Block` push before the dispatcher gives up
+    pub fn block_timeout(&self) -> Duration {
+        self.block_timeout
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().buffer.len()
+    }
+
+    // return the message back if the queue is still full after `BLOCK_TIMEOUT`
+    pub fn push(&self, msg: Arc<MsgPayload>) -> Result<(), Arc<MsgPayload>> {
+        let mut state = self.state.lock().unwrap();
+
+        // keep order, spool until all spooled messages are replayed
+        // a `Block` queue only spools after `spill`
+        let is_full = state.buffer.len() >= self.capacity;
+        let is_spool = self.policy == OverflowPolicy::Spool;
+        if let Some(spool) = state.spool.as_mut() {
+            if (is_full && is_spool) || spool.is_active() {
+                match spool.push(&msg) {
+                    Ok(()) => {
+                        self.cond.notify_all();
+                        return Ok(());
+                    }
+                    Err(e) if is_spool => {
+                        log::error!("Spool message failed: {e} - {}", self.name);
+                        state.dropped += 1;
+                        self.cond.notify_all();
+                        return Ok(());
+                    }
+                    // never dropped, wait for room below
+                    Err(e) => log::error!("Spool message failed: {e} - {}", self.name),
+                }
+            }
+        }
+
+        match self.policy {
+            OverflowPolicy::Block => {
+                while state.buffer.len() >= self.capacity {
+                    let (next, ret) = self.cond.wait_timeout(state, BLOCK_TIMEOUT).unwrap();
+                    state = next;
+                    if ret.timed_out() && state.buffer.len() >= self.capacity {
+                        return Err(msg);
+                    }
+                }
+            }
+            OverflowPolicy::DropOldest => {
+                if is_full {
+                    state.buffer.pop_front();
+                    state.dropped += 1;
+                    if state.dropped % DROP_REPORT_INTERVAL == 1 {
+                        log::warn!("Queue of {} is full, dropped: {}", self.name, state.dropped);
+                    }
+                }
+            }
+            OverflowPolicy::Spool => (),
+        }
+
+        state.buffer.push_back(msg);
+        self.cond.notify_all();
+        Ok(())
+    }
+
+    // overflow of a stalled `Block` queue, the message and the following ones are
+    // spooled until the sink has replayed them, return the message back if it can't be spooled
+    pub fn spill(&self, msg: Arc<MsgPayload>) -> Result<(), Arc<MsgPayload>> {
+        let mut state = self.state.lock().unwrap();
+        let spool = match state.spool.as_mut() {
+            Some(spool) => spool,
+            None => match Spool::open(&self.spool_directory, &self.name) {
+                Ok(spool) => state.spool.insert(spool),
+                Err(e) => {
+                    log::error!("Open spool failed: {e} - {}", self.name);
+                    return Err(msg);
+                }
+            },
+        };
+        if let Err(e) = spool.push(&msg) {
+            log::error!("Spool message failed: {e} - {}", self.name);
+            return Err(msg);
+        }
+        self.cond.notify_all();
+        Ok(())
+    }
+
+    // wait for the next message, None if closed and the memory buffer is empty,
+    // spooled messages are left for the next run
+    pub fn pop(&self) -> Option<Arc<MsgPayload>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(msg) = state.buffer.pop_front() {
+                self.cond.notify_all();
+                return Some(msg);
+            }
+            if state.closed {
+                return None;
+            }
+            if let Some(msg) = state.spool.as_mut().and_then(|val| val.pop()) {
+                return Some(Arc::new(msg));
+            }
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        if let Some(spool) = state.spool.as_ref().filter(|val| val.is_active()) {
+            log::info!(target: "info", "Spool of {} is kept: {:?}", self.name, spool.path);
+        }
+        if state.dropped > 0 {
+            log::warn!("Queue of {} dropped {} messages.", self.name, state.dropped);
+        }
+        self.cond.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::remove_dir_all, thread};
+
+    use super::*;
+
+    fn message(idx: usize) -> Arc<MsgPayload> {
+        Arc::new(MsgPayload::new("CWB", "MN", vec![idx.to_string()]))
+    }
+
+    fn values(queue: &SinkQueue) -> Vec<String> {
+        let mut result = Vec::new();
+        while let Some(msg) = queue.pop() {
+            result.push(msg.value[0].clone());
+        }
+        result
+    }
+
+    fn expected(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|idx| idx.to_string()).collect()
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let queue = SinkQueue::new("drop", 3, OverflowPolicy::DropOldest, "").unwrap();
+        for idx in 0..5 {
+            queue.push(message(idx)).unwrap();
+        }
+        queue.close();
+        assert_eq!(values(&queue), expected(2..5));
+    }
+
+    #[test]
+    fn block_until_popped() {
+        let queue = Arc::new(SinkQueue::new("block", 2, OverflowPolicy::Block, "").unwrap());
+        queue.push(message(0)).unwrap();
+        queue.push(message(1)).unwrap();
+        assert!(queue.push(message(2)).is_err());
+
+        let consumer = queue.clone();
+        let handle = thread::spawn(move || consumer.pop());
+        queue.push(message(2)).unwrap();
+        assert_eq!(handle.join().unwrap().unwrap().value[0], "0");
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn spool_and_replay() {
+        let directory = std::env::temp_dir().join("naho_spool_test");
+        let directory = directory.to_str().unwrap();
+        let _ = remove_dir_all(directory);
+
+        let queue = SinkQueue::new("spool", 2, OverflowPolicy::Spool, directory).unwrap();
+        for idx in 0..5 {
+            queue.push(message(idx)).unwrap();
+        }
+        assert_eq!(queue.pop().unwrap().value[0], "0");
+        // spool is active, keep order
+        queue.push(message(5)).unwrap();
+        assert_eq!(queue.len(), 1);
+        queue.close();
+        assert_eq!(values(&queue), expected(1..2));
+
+        // spooled messages are replayed by the next run
+        let queue = SinkQueue::new("spool", 2, OverflowPolicy::Spool, directory).unwrap();
+        let consumer = thread::spawn({
+            let queue = Arc::new(queue);
+            move || {
+                let mut result = Vec::new();
+                for _ in 0..4 {
+                    result.push(queue.pop().unwrap().value[0].clone());
+                }
+                result
+            }
+        });
+        assert_eq!(consumer.join().unwrap(), expected(2..6));
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn resume_replay() {
+        let directory = std::env::temp_dir().join("naho_spool_resume_test");
+        let directory = directory.to_str().unwrap();
+        let _ = remove_dir_all(directory);
+
+        let queue = SinkQueue::new("spool", 2, OverflowPolicy::Spool, directory).unwrap();
+        for idx in 0..5 {
+            queue.push(message(idx)).unwrap();
+        }
+        // 2 is replayed from the spool, then the process is killed
+        assert_eq!(queue.pop().unwrap().value[0], "0");
+        assert_eq!(queue.pop().unwrap().value[0], "1");
+        assert_eq!(queue.pop().unwrap().value[0], "2");
+        drop(queue);
+
+        let queue = SinkQueue::new("spool", 2, OverflowPolicy::Spool, directory).unwrap();
+        assert_eq!(queue.pop().unwrap().value[0], "3");
+        assert_eq!(queue.pop().unwrap().value[0], "4");
+        let mut state = queue.state.lock().unwrap();
+        assert!(state.spool.as_mut().unwrap().pop().is_none());
+        assert!(!Path::new(directory).join("spool.replay").exists());
+        assert!(!Path::new(directory).join("spool.offset").exists());
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn block_spill() {
+        let directory = std::env::temp_dir().join("naho_spill_test");
+        let directory = directory.to_str().unwrap();
+        let _ = remove_dir_all(directory);
+
+        let queue = SinkQueue::new("spill", 1, OverflowPolicy::Block, directory).unwrap();
+        queue.push(message(0)).unwrap();
+        assert!(queue.push(message(1)).is_err());
+        queue.spill(message(1)).unwrap();
+        // spool is active, keep order without waiting
+        queue.push(message(2)).unwrap();
+        assert_eq!(queue.len(), 1);
+        for idx in 0..3 {
+            assert_eq!(queue.pop().unwrap().value[0], idx.to_string());
+        }
+        // replayed, a full queue waits again
+        queue.push(message(3)).unwrap();
+        assert!(queue.push(message(4)).is_err());
+
+        // spool left on shutdown is replayed on next start
+        queue.spill(message(4)).unwrap();
+        queue.close();
+        assert_eq!(values(&queue), expected(3..4));
+        let queue = SinkQueue::new("spill", 1, OverflowPolicy::Block, directory).unwrap();
+        assert_eq!(queue.pop().unwrap().value[0], "4");
+        remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,20 +1,658 @@
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     error::Error,
-    fs::{remove_file, rename, File},
-    io::{BufRead, BufReader},
-    path::Path,
-    sync::{mpsc::Sender, Arc},
-    thread::{self, sleep, JoinHandle},
-    time::Duration,
+    fs::{create_dir_all, metadata, read_dir, remove_file, rename, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::{mpsc::SyncSender, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
-use crate::{component::utils::files::is_update_header, config::SystemConfig};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
 
-use super::{utils::files::HeaderTable, MsgPayload};
+use crate::{
+    component::utils::files::is_update_header,
+    config::{ListenConfig, SystemConfig},
+};
+
+use super::{
+    utils::{
+        files::HeaderTable,
+        shutdown::Shutdown,
+        watcher::{expand_path, is_glob_pattern, watch_dir, DirWatcher},
+    },
+    MsgPayload,
+};
+
+const BATCH_SIZE: usize = 100;
+// archive directory of a `dir` target
+const DONE_DIR: &str = "done";
+// a FIFO is polled often, the writer is blocked when its buffer is full
+const PIPE_INTERVAL: Duration = Duration::from_millis(500);
+
+// read position of a file which is left in place, persisted in `listen_state`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TailState {
+    pub path: String,
+    pub inode: u64,
+    pub offset: u64,
+}
+
+// listen target name (`name:path` for pattern targets) -> state
+pub type TailStateTable = HashMap<String, TailState>;
+
+pub fn load_tail_state<P>(path: P) -> TailStateTable
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !path.exists() {
+        return TailStateTable::new();
+    }
+    let ret = File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string()));
+    match ret {
+        Ok(table) => table,
+        Err(e) => {
+            log::error!("Load listen state failed: {e} - {path:?}");
+            TailStateTable::new()
+        }
+    }
+}
+
+pub fn save_tail_state<P>(path: P, table: &TailStateTable) -> Result<(), Box<dyn Error + 'static>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if let Some(root) = path.parent() {
+        create_dir_all(root)?;
+    }
+    // replaced at once, the state is never half written
+    let tmp_path = path.with_extension("tmp");
+    serde_json::to_writer_pretty(File::create(&tmp_path)?, table)?;
+    rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// next line, invalid UTF-8 is replaced instead of stopping the read
+// None at the end of file or on a read error, `buffer` keeps the raw bytes
+fn read_line_lossy<R>(reader: &mut R, buffer: &mut Vec<u8>) -> Option<String>
+where
+    R: BufRead,
+{
+    buffer.clear();
+    match reader.read_until(b'\n', buffer) {
+        Ok(0) => None,
+        Ok(_) => {
+            let line = String::from_utf8_lossy(buffer);
+            if let Cow::Owned(_) = line {
+                log::warn!("Invalid UTF-8 replaced: {line:?}");
+            }
+            Some(line.into_owned())
+        }
+        Err(e) => {
+            log::error!("Read line failed: {e}");
+            None
+        }
+    }
+}
+
+// `number` lines, None if the file is shorter
+fn read_header<R>(reader: &mut R, number: usize) -> Option<Vec<String>>
+where
+    R: BufRead,
+{
+    let mut header = Vec::new();
+    let mut buffer = Vec::new();
+    for _ in 0..number {
+        let Some(line) = read_line_lossy(reader, &mut buffer) else {
+            break;
+        };
+        header.push(line);
+    }
+    // error is recorded in previous for loop
+    if header.len() < number {
+        return None;
+    }
+    Some(header)
+}
+
+// send the header if it is changed, return false if the header isn't complete
+fn send_header<R>(
+    reader: &mut R,
+    listen_target: &ListenConfig,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> bool
+where
+    R: BufRead,
+{
+    let Some(cfg_header) = listen_target.header.as_ref() else {
+        return true;
+    };
+    let Some(header) = read_header(reader, cfg_header.number) else {
+        return false;
+    };
+
+    if is_update_header(header_table, filename, &header) {
+        // consume header
+        let mut msg = MsgPayload::new(&listen_target.tag, &listen_target.dkind, header);
+        if let Err(e) = msg.set_update_header() {
+            log::error!("Setup update header flag failed: {e}");
+        }
+
+        if let Err(e) = sender.send(Arc::new(msg)) {
+            log::error!("Send header failed: {e}");
+        }
+    }
+    true
+}
+
+fn send_values(
+    listen_target: &ListenConfig,
+    values: Vec<String>,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) {
+    let msg = MsgPayload::new(&listen_target.tag, &listen_target.dkind, values);
+    if let Err(e) = sender.send(Arc::new(msg)) {
+        log::error!("Send data failed: {e}");
+    }
+}
+
+// complete lines from the current position, return (lines, bytes of the lines)
+// a last line without newline is left for the next read
+fn send_lines<R>(
+    reader: &mut R,
+    listen_target: &ListenConfig,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> (usize, u64)
+where
+    R: BufRead,
+{
+    let mut buffer = Vec::new();
+    let mut total = 0;
+    let mut length = 0;
+    let mut values = Vec::new();
+    while let Some(line) = read_line_lossy(reader, &mut buffer) {
+        if !line.ends_with('\n') {
+            break;
+        }
+        if values.len() >= BATCH_SIZE {
+            send_values(listen_target, values, sender);
+            values = Vec::new();
+        }
+
+        values.push(line);
+        length += buffer.len() as u64;
+        total += 1;
+    }
+    if !values.is_empty() {
+        send_values(listen_target, values, sender);
+    }
+    (total, length)
+}
+
+// header and all rows of a file, None if the header isn't complete
+fn send_file<P>(
+    path: P,
+    listen_target: &ListenConfig,
+    header_key: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> Result<Option<usize>, Box<dyn Error + 'static>>
+where
+    P: AsRef<Path>,
+{
+    let mut reader = BufReader::new(File::open(path)?);
+    if !send_header(&mut reader, listen_target, header_key, header_table, sender) {
+        return Ok(None);
+    }
+
+    let mut buffer = Vec::new();
+    let mut total = 0;
+    let mut values = Vec::new();
+    while let Some(line) = read_line_lossy(&mut reader, &mut buffer) {
+        if values.len() >= BATCH_SIZE {
+            send_values(listen_target, values, sender);
+            values = Vec::new();
+        }
+
+        values.push(line);
+        total += 1;
+    }
+    if !values.is_empty() {
+        send_values(listen_target, values, sender);
+    }
+    Ok(Some(total))
+}
+
+// the whole file is sent, then it's moved or removed by `flags`
+fn listen_whole_file(
+    config: &SystemConfig,
+    listen_target: &ListenConfig,
+    cfg_listen_path: &Path,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> usize {
+    let mut listen_file = cfg_listen_path.to_string_lossy().to_string();
+    let mut do_remove_file = false;
+    if let Some(flags) = &listen_target.flags {
+        if flags.f_move == Some(true) {
+            let suffix = get_move_suffix(config);
+            listen_file = format!("{listen_file}.{suffix}");
+            if let Err(e) = rename(cfg_listen_path, &listen_file) {
+                log::error!("System Error: {e}");
+                return 0;
+            }
+        }
+
+        if flags.f_remove_after_used == Some(true) {
+            do_remove_file = true;
+        }
+    }
+
+    let ret = send_file(&listen_file, listen_target, filename, header_table, sender);
+    let total = match ret {
+        Ok(Some(total)) => total,
+        Ok(None) => return 0,
+        Err(e) => {
+            log::error!("System Error. {e}");
+            return 0;
+        }
+    };
+
+    if do_remove_file {
+        if let Err(e) = remove_file(&listen_file) {
+            log::error!("Remove file failed: {e}");
+        }
+    }
+    log::info!(target: "info", "Listened {}", &listen_file);
+    total
+}
+
+fn get_move_suffix(config: &SystemConfig) -> &str {
+    config
+        .global
+        .listen_move_suffix
+        .as_deref()
+        .unwrap_or("lock")
+}
+
+// each file matched by a pattern target has its own state
+fn tail_key(listen_target: &ListenConfig, path: &str) -> String {
+    if is_glob_pattern(&listen_target.path) {
+        format!("{}:{path}", listen_target.name)
+    } else {
+        listen_target.name.clone()
+    }
+}
+
+// rows after `offset`, the header is checked first
+// return (rows, offset after the last complete line), None if the header isn't complete
+fn send_tail(
+    file: File,
+    offset: u64,
+    listen_target: &ListenConfig,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> Result<Option<(usize, u64)>, Box<dyn Error + 'static>> {
+    // header is checked on each read, it is sent again if the file is replaced
+    let mut reader = BufReader::new(file);
+    if !send_header(&mut reader, listen_target, filename, header_table, sender) {
+        return Ok(None);
+    }
+    let offset = offset.max(reader.stream_position()?);
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let (total, length) = send_lines(&mut reader, listen_target, sender);
+    Ok(Some((total, offset + length)))
+}
+
+// a rotated file is renamed in the same directory, it's found by the inode
+fn find_rotated(state: &TailState) -> Option<PathBuf> {
+    let dir = Path::new(&state.path).parent()?;
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    read_dir(dir)
+        .ok()?
+        .filter_map(|val| val.ok())
+        .map(|val| val.path())
+        .find(|val| metadata(val).is_ok_and(|meta| meta.is_file() && meta.ino() == state.inode))
+}
+
+// rows appended to a file after the last read and before it was rotated
+fn send_rotated(
+    state: &TailState,
+    listen_target: &ListenConfig,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> usize {
+    let Some(rotated) = find_rotated(state) else {
+        log::warn!(
+            "File rotated: {:?}, rotated file not found, rows after offset {} are lost",
+            state.path,
+            state.offset
+        );
+        return 0;
+    };
+    let ret = File::open(&rotated).map_err(|e| e.into()).and_then(|file| {
+        send_tail(
+            file,
+            state.offset,
+            listen_target,
+            filename,
+            header_table,
+            sender,
+        )
+    });
+    match ret {
+        Ok(Some((total, _))) => {
+            log::info!(target: "info", "Listened {total} rows from rotated {rotated:?}");
+            total
+        }
+        Ok(None) => 0,
+        Err(e) => {
+            log::error!("Read rotated file failed: {e} - {rotated:?}");
+            0
+        }
+    }
+}
+
+// only rows appended since the last read are sent, return (rows, state changed)
+fn listen_tail(
+    listen_target: &ListenConfig,
+    path: &Path,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    tail_table: &mut TailStateTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> Result<(usize, bool), Box<dyn Error + 'static>> {
+    let path = path.to_string_lossy().to_string();
+    let key = tail_key(listen_target, &path);
+    let file = File::open(&path)?;
+    let meta = file.metadata()?;
+    let inode = meta.ino();
+
+    let mut rotated = 0;
+    let offset = match tail_table.get(&key) {
+        Some(state) if state.path != path => 0,
+        Some(state) if state.inode != inode => {
+            log::warn!("File rotated: {path:?}, read from start");
+            let state = state.clone();
+            rotated = send_rotated(&state, listen_target, filename, header_table, sender);
+            0
+        }
+        Some(state) if meta.len() < state.offset => {
+            log::warn!("File truncated: {path:?}, read from start");
+            0
+        }
+        Some(state) => state.offset,
+        None => 0,
+    };
+    if offset == meta.len() && offset > 0 {
+        return Ok((0, false));
+    }
+
+    let Some((total, offset)) =
+        send_tail(file, offset, listen_target, filename, header_table, sender)?
+    else {
+        return Ok((rotated, false));
+    };
+    let total = total + rotated;
+    let state = TailState {
+        path: path.clone(),
+        inode,
+        offset,
+    };
+    let changed = tail_table.get(&key) != Some(&state);
+    tail_table.insert(key, state);
+    if total > 0 {
+        log::info!(target: "info", "Listened {total} rows from {path}");
+    }
+    Ok((total, changed))
+}
+
+// files matched by `pattern` in the drop folder `path`
+fn drop_pattern(listen_target: &ListenConfig) -> String {
+    Path::new(&listen_target.path)
+        .join(listen_target.get_pattern())
+        .to_string_lossy()
+        .to_string()
+}
+
+// a file of the drop folder is sent whole, then it's archived to `done/`
+// the header is checked per target, files of a logger share the same header
+fn listen_drop_file(
+    listen_target: &ListenConfig,
+    path: &Path,
+    filename: &str,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> Result<usize, Box<dyn Error + 'static>> {
+    // file is left until the header is written
+    let Some(total) = send_file(
+        path,
+        listen_target,
+        &listen_target.name,
+        header_table,
+        sender,
+    )?
+    else {
+        return Ok(0);
+    };
+
+    let done_dir = Path::new(&listen_target.path).join(DONE_DIR);
+    create_dir_all(&done_dir)?;
+    let mut archive = done_dir.join(filename);
+    if archive.exists() {
+        let suffix = Local::now().format("%Y%m%d%H%M%S");
+        archive = done_dir.join(format!("{filename}.{suffix}"));
+    }
+    rename(path, &archive)?;
+    log::info!(target: "info", "Listened {path:?}, archived to {archive:?}");
+    Ok(total)
+}
+
+// size and modified time of a drop file, and when they were seen first
+#[derive(Debug, Clone)]
+struct DropState {
+    len: u64,
+    modified: SystemTime,
+    seen_at: Instant,
+}
+
+type DropStateTable = HashMap<PathBuf, DropState>;
 
-pub fn setup_file_listen_naho(
+// a drop file may still be written by the logger,
+// it's stable when size and modified time are unchanged for `settle`
+fn is_drop_file_stable(path: &Path, drop_table: &mut DropStateTable, settle: Duration) -> bool {
+    let Ok(meta) = metadata(path) else {
+        return false;
+    };
+    let Ok(modified) = meta.modified() else {
+        return false;
+    };
+    match drop_table.get(path) {
+        Some(state) if state.len == meta.len() && state.modified == modified => {
+            state.seen_at.elapsed() >= settle
+        }
+        _ => {
+            let state = DropState {
+                len: meta.len(),
+                modified,
+                seen_at: Instant::now(),
+            };
+            drop_table.insert(path.to_path_buf(), state);
+            false
+        }
+    }
+}
+
+// stable files of the drop folder, return the number of rows
+fn listen_drop_dir(
+    listen_target: &ListenConfig,
+    drop_table: &mut DropStateTable,
+    settle: Duration,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> usize {
+    let pattern = drop_pattern(listen_target);
+    let paths = expand_path(&pattern);
+    // forget files which are archived or removed
+    let dir = Path::new(&listen_target.path);
+    drop_table.retain(|path, _| !path.starts_with(dir) || paths.contains(path));
+
+    let mut total = 0;
+    for path in paths {
+        let Some(filename) = path.file_name().and_then(|val| val.to_str()) else {
+            continue;
+        };
+        if !is_drop_file_stable(&path, drop_table, settle) {
+            continue;
+        }
+        match listen_drop_file(listen_target, &path, filename, header_table, sender) {
+            Ok(num) => total += num,
+            Err(e) => log::error!("Listen failed: {e} - {path:?}"),
+        }
+    }
+    total
+}
+
+// read end of a FIFO opened without blocking
+// the header is expected again after the writer closes the FIFO
+#[derive(Debug, Default)]
+struct PipeState {
+    file: Option<File>,
+    buffer: Vec<u8>,
+    header: Vec<String>,
+}
+
+fn open_pipe(path: &Path) -> Result<File, Box<dyn Error + 'static>> {
+    if !metadata(path)?.file_type().is_fifo() {
+        return Err(format!("Not a named pipe: {path:?}").into());
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    Ok(file)
+}
+
+// complete lines written to the FIFO since the last read
+fn listen_pipe(
+    listen_target: &ListenConfig,
+    state: &mut PipeState,
+    header_table: &mut HeaderTable,
+    sender: &SyncSender<Arc<MsgPayload>>,
+) -> Result<usize, Box<dyn Error + 'static>> {
+    let path = Path::new(&listen_target.path);
+    if state.file.is_none() {
+        state.file = Some(open_pipe(path)?);
+    }
+    let Some(file) = state.file.as_mut() else {
+        return Ok(0);
+    };
+
+    // read 0 byte: no writer now
+    let mut closed = false;
+    let mut chunk = [0; 4096];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => {
+                closed = true;
+                break;
+            }
+            Ok(num) => state.buffer.extend_from_slice(&chunk[..num]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                state.file = None;
+                return Err(e.into());
+            }
+        }
+    }
+
+    let number = listen_target.header.as_ref().map_or(0, |val| val.number);
+    let mut total = 0;
+    let mut values = Vec::new();
+    while let Some(pos) = state.buffer.iter().position(|&val| val == b'\n') {
+        let line = state.buffer.drain(..=pos).collect::<Vec<_>>();
+        let line = String::from_utf8_lossy(&line).to_string();
+        if state.header.len() < number {
+            state.header.push(line);
+            if state.header.len() == number {
+                let header = state.header.concat();
+                send_header(
+                    &mut header.as_bytes(),
+                    listen_target,
+                    &listen_target.name,
+                    header_table,
+                    sender,
+                );
+            }
+            continue;
+        }
+        if values.len() >= BATCH_SIZE {
+            send_values(listen_target, values, sender);
+            values = Vec::new();
+        }
+        values.push(line);
+        total += 1;
+    }
+    if !values.is_empty() {
+        send_values(listen_target, values, sender);
+    }
+
+    if closed {
+        if !state.buffer.is_empty() {
+            log::warn!("Incomplete line dropped: {path:?}");
+            state.buffer.clear();
+        }
+        state.header.clear();
+    }
+    if total > 0 {
+        log::info!(target: "info", "Listened {total} rows from {path:?}");
+    }
+    Ok(total)
+}
+
+// a file is followed by `listen_tail` unless it's moved or removed after read
+fn is_tail_target(listen_target: &ListenConfig) -> bool {
+    let Some(flags) = listen_target.flags.as_ref() else {
+        return true;
+    };
+    flags.f_move != Some(true) && flags.f_remove_after_used != Some(true)
+}
+
+// `dir` target watches the drop folder itself
+fn watch_target_dir(listen_target: &ListenConfig) -> Option<PathBuf> {
+    if listen_target.ftype == "dir" {
+        if is_glob_pattern(&listen_target.path) {
+            return None;
+        }
+        return Some(PathBuf::from(&listen_target.path));
+    }
+    watch_dir(&listen_target.path)
+}
+
+// every listen target with a codec entry of its tag
+pub fn setup_file_listen(
     config: Arc<SystemConfig>,
-    sender: Sender<Arc<MsgPayload>>,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
 ) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
     let mut listen_list = Vec::new();
     let mut header_table = HeaderTable::new();
@@ -25,142 +663,330 @@ pub fn setup_file_listen_naho(
     };
 
     for val in cfg_listen_list {
-        if val.tag == "NAHO" {
+        if config.codec.contains_key(&val.tag) {
             listen_list.push(val.clone());
+        } else {
+            log::error!("Unsupport tag: {:?} - listen target {}", val.tag, val.name);
         }
     }
 
     if listen_list.is_empty() {
-        log::info!("No listen target with tag: NAHO.");
-        return Err(String::from("No listen target with tag: NAHO.").into());
+        log::info!("No listen target with codec.");
+        return Err(String::from("No listen target with codec.").into());
     }
 
+    let state_path = config.global.get_listen_state().to_string();
+    let mut tail_table = load_tail_state(&state_path);
+
+    let mut interval = config.global.get_listen_interval();
+    // drop files are sent after they are unchanged for a polling interval
+    let settle = interval;
+    let mut drop_table = DropStateTable::new();
+    let mut watch_dirs = Vec::new();
+    for listen_target in listen_list.iter() {
+        if listen_target.ftype == "pipe" {
+            interval = interval.min(PIPE_INTERVAL);
+            continue;
+        }
+        if !config.global.get_listen_watch() {
+            continue;
+        }
+        match watch_target_dir(listen_target) {
+            Some(dir) if !watch_dirs.contains(&dir) => watch_dirs.push(dir),
+            Some(_) => {}
+            None => log::warn!(
+                "Directory of {:?} is a pattern, fallback to polling",
+                listen_target.path
+            ),
+        }
+    }
+    let mut pipe_table: HashMap<String, PipeState> = HashMap::new();
+
     let handle = thread::spawn(move || {
-        loop {
-            // 1. move original file
-            // 2. send data
+        let mut total = 0;
+        let mut watcher = DirWatcher::new(&watch_dirs);
+        if watcher.is_watching() {
+            log::info!(target: "info", "Loggernet listener watching {watch_dirs:?}");
+        }
+        while !shutdown.is_triggered() {
+            let mut changed = false;
             for listen_target in listen_list.iter() {
-                let cfg_listen_path = Path::new(&listen_target.path);
-                let Some(filename) = cfg_listen_path.file_name() else {
-                    log::error!("System Error. cfg_listen_path: {cfg_listen_path:?}");
-                    continue;
-                };
-                let Some(filename) = filename.to_str() else {
-                    log::error!("System Error. cfg_listen_path: {cfg_listen_path:?}");
-                    continue;
-                };
-
-                if !cfg_listen_path.exists() {
-                    continue;
-                }
-
-                let mut listen_file = cfg_listen_path.to_string_lossy().to_string();
-                let mut do_remove_file = false;
-                if let Some(flags) = &listen_target.flags {
-                    if flags.f_move == Some(true) {
-                        let suffix =
-                            if let Some(suffix) = config.global.listen_move_suffix.as_deref() {
-                                suffix
-                            } else {
-                                "lock"
-                            };
-
-                        listen_file = format!("{listen_file}.{suffix}");
-                        if let Err(e) = rename(cfg_listen_path, &listen_file) {
-                            log::error!("System Error: {e}");
-                            continue;
+                match listen_target.ftype.as_str() {
+                    "pipe" => {
+                        let state = pipe_table.entry(listen_target.name.clone()).or_default();
+                        match listen_pipe(listen_target, state, &mut header_table, &sender) {
+                            Ok(num) => total += num,
+                            Err(e) => log::error!("Listen failed: {e} - {:?}", listen_target.path),
                         }
-                    }
-
-                    if flags.f_remove_after_used == Some(true) {
-                        do_remove_file = true;
-                    }
-                }
-
-                let mut buffer = String::new();
-                let file = match File::open(&listen_file) {
-                    Ok(file) => file,
-                    Err(e) => {
-                        log::error!("System Error. {e}");
                         continue;
                     }
-                };
-                let mut reader = BufReader::new(file);
-
-                if let Some(cfg_header) = listen_target.header.as_ref() {
-                    let number = cfg_header.number;
-                    let mut header = Vec::new();
-                    // header
-                    for _ in 0..number {
-                        match reader.read_line(&mut buffer) {
-                            Ok(num) => {
-                                if num == 0 {
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("System Error. {e}");
-                                break;
-                            }
-                        }
-                        header.push(buffer.to_string());
-                        buffer.clear();
-                    }
-
-                    // error is recorded in previous for loop
-                    if header.len() < number {
+                    "dir" => {
+                        total += listen_drop_dir(
+                            listen_target,
+                            &mut drop_table,
+                            settle,
+                            &mut header_table,
+                            &sender,
+                        );
                         continue;
                     }
+                    _ => {}
+                }
 
-                    if is_update_header(&mut header_table, filename, &header) {
-                        // consume header
-                        let mut msg =
-                            MsgPayload::new(&listen_target.tag, &listen_target.dkind, header);
-                        if let Err(e) = msg.set_update_header() {
-                            log::error!("Setup update header flag failed: {e}");
-                        }
+                for cfg_listen_path in expand_path(&listen_target.path) {
+                    let Some(filename) = cfg_listen_path.file_name().and_then(|val| val.to_str())
+                    else {
+                        log::error!("System Error. cfg_listen_path: {cfg_listen_path:?}");
+                        continue;
+                    };
 
-                        if let Err(e) = sender.send(Arc::new(msg)) {
-                            log::error!("Send header failed: {e}");
+                    if !is_tail_target(listen_target) {
+                        // moved file is matched by a wide pattern
+                        let suffix = format!(".{}", get_move_suffix(&config));
+                        if filename.ends_with(&suffix) {
+                            continue;
                         }
+                        total += listen_whole_file(
+                            &config,
+                            listen_target,
+                            &cfg_listen_path,
+                            filename,
+                            &mut header_table,
+                            &sender,
+                        );
+                        continue;
                     }
-                }
 
-                buffer.clear();
-                let mut counter = 0;
-                let mut values = Vec::new();
-                while let Ok(num) = reader.read_line(&mut buffer) {
-                    if num == 0 {
-                        break;
-                    }
-                    if counter >= 100 {
-                        let msg = MsgPayload::new(&listen_target.tag, &listen_target.dkind, values);
-                        if let Err(e) = sender.send(Arc::new(msg)) {
-                            log::error!("Send data failed: {e}");
+                    match listen_tail(
+                        listen_target,
+                        &cfg_listen_path,
+                        filename,
+                        &mut header_table,
+                        &mut tail_table,
+                        &sender,
+                    ) {
+                        Ok((num, is_changed)) => {
+                            total += num;
+                            changed |= is_changed;
                         }
-                        values = Vec::new();
-                        counter = 0;
+                        Err(e) => log::error!("Listen failed: {e} - {cfg_listen_path:?}"),
                     }
-
-                    values.push(buffer.clone());
-                    buffer.clear();
-                    counter += 1;
-                }
-
-                let msg = MsgPayload::new(&listen_target.tag, &listen_target.dkind, values);
-                if let Err(e) = sender.send(Arc::new(msg)) {
-                    log::error!("Send data failed: {e}");
                 }
+            }
 
-                if do_remove_file {
-                    if let Err(e) = remove_file(&listen_file) {
-                        log::error!("Remove file failed: {e}");
-                    }
+            if changed {
+                if let Err(e) = save_tail_state(&state_path, &tail_table) {
+                    log::error!("Save listen state failed: {e} - {state_path:?}");
                 }
-                log::info!(target: "info", "Listened {}", &listen_file);
             }
-            sleep(Duration::from_secs(5));
+            watcher.wait(interval, &shutdown);
         }
+        log::info!(target: "info", "Loggernet listener stopped.");
+        total
     });
     Ok(handle)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs::OpenOptions, io::Write, sync::mpsc};
+
+    use super::*;
+
+    fn listen_target(path: &Path) -> ListenConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "path": path,
+            "ftype": "file",
+            "tag": "NAHO",
+            "dkind": "Min",
+            "header": {"number": 1}
+        }))
+        .unwrap()
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn tail_follow() {
+        let root = std::env::temp_dir().join("naho_tail_test");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        let path = root.join("data.dat");
+        let target = listen_target(&path);
+        let (tx, rx) = mpsc::sync_channel(16);
+        let mut header_table = HeaderTable::new();
+        let mut tail_table = TailStateTable::new();
+        let mut listen = |tail_table: &mut TailStateTable| {
+            listen_tail(
+                &target,
+                &path,
+                "data.dat",
+                &mut header_table,
+                tail_table,
+                &tx,
+            )
+            .unwrap()
+            .0
+        };
+
+        // partial last line is kept for the next read
+        append(&path, "header\n1,a\n2,b\n3,");
+        assert_eq!(listen(&mut tail_table), 2);
+        assert!(rx.recv().unwrap().update_header);
+        assert_eq!(rx.recv().unwrap().value, vec!["1,a\n", "2,b\n"]);
+        assert_eq!(listen(&mut tail_table), 0);
+
+        append(&path, "c\n");
+        assert_eq!(listen(&mut tail_table), 1);
+        assert_eq!(rx.recv().unwrap().value, vec!["3,c\n"]);
+
+        // state is restored after restart
+        let state_path = root.join("state.json");
+        save_tail_state(&state_path, &tail_table).unwrap();
+        let mut tail_table = load_tail_state(&state_path);
+        append(&path, "4,d\n");
+        assert_eq!(listen(&mut tail_table), 1);
+        assert_eq!(rx.recv().unwrap().value, vec!["4,d\n"]);
+
+        // truncated file is read from start
+        std::fs::write(&path, "header\n5,e\n").unwrap();
+        assert_eq!(listen(&mut tail_table), 1);
+        assert_eq!(rx.recv().unwrap().value, vec!["5,e\n"]);
+
+        // unread rows of the rotated file are sent first
+        // the new file is read from start, the header is unchanged
+        append(&path, "5,f\n");
+        let rotated = root.join("data.dat.1");
+        rename(&path, &rotated).unwrap();
+        append(&path, "header\n6,f\n7,g\n");
+        assert_eq!(listen(&mut tail_table), 3);
+        assert_eq!(rx.recv().unwrap().value, vec!["5,f\n"]);
+        assert_eq!(rx.recv().unwrap().value, vec!["6,f\n", "7,g\n"]);
+
+        // invalid UTF-8 is replaced, the following rows are still read
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"8,\xff\n9,i\n").unwrap();
+        assert_eq!(listen(&mut tail_table), 2);
+        assert_eq!(rx.recv().unwrap().value, vec!["8,\u{fffd}\n", "9,i\n"]);
+        assert!(rx.try_recv().is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn pipe_stream() {
+        let root = std::env::temp_dir().join("naho_pipe_test");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        let path = root.join("data.fifo");
+        let cpath = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o644) }, 0);
+        let target = listen_target(&path);
+        let (tx, rx) = mpsc::sync_channel(16);
+        let mut header_table = HeaderTable::new();
+        let mut state = PipeState::default();
+
+        // no writer yet
+        assert_eq!(
+            listen_pipe(&target, &mut state, &mut header_table, &tx).unwrap(),
+            0
+        );
+
+        let mut writer = OpenOptions::new().write(true).open(&path).unwrap();
+        writer.write_all(b"header\n1,a\n2,").unwrap();
+        assert_eq!(
+            listen_pipe(&target, &mut state, &mut header_table, &tx).unwrap(),
+            1
+        );
+        assert!(rx.recv().unwrap().update_header);
+        assert_eq!(rx.recv().unwrap().value, vec!["1,a\n"]);
+        writer.write_all(b"b\n").unwrap();
+        drop(writer);
+        assert_eq!(
+            listen_pipe(&target, &mut state, &mut header_table, &tx).unwrap(),
+            1
+        );
+        assert_eq!(rx.recv().unwrap().value, vec!["2,b\n"]);
+
+        // next writer starts with the header again
+        let mut writer = OpenOptions::new().write(true).open(&path).unwrap();
+        writer.write_all(b"header\n3,c\n").unwrap();
+        drop(writer);
+        assert_eq!(
+            listen_pipe(&target, &mut state, &mut header_table, &tx).unwrap(),
+            1
+        );
+        assert_eq!(rx.recv().unwrap().value, vec!["3,c\n"]);
+        assert!(rx.try_recv().is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn drop_folder() {
+        let root = std::env::temp_dir().join("naho_drop_test");
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        let mut target = listen_target(&root);
+        target.ftype = "dir".to_string();
+        target.pattern = Some("*.dat".to_string());
+        let (tx, rx) = mpsc::sync_channel(16);
+        let mut header_table = HeaderTable::new();
+        let mut drop_table = DropStateTable::new();
+        let mut listen = |header_table: &mut HeaderTable| {
+            listen_drop_dir(&target, &mut drop_table, Duration::ZERO, header_table, &tx)
+        };
+
+        append(&root.join("a.dat"), "header\n1,a\n");
+        append(&root.join("b.dat"), "header\n2,b\n");
+        append(&root.join("c.txt"), "header\n3,c\n");
+        let paths = expand_path(&drop_pattern(&target));
+        assert_eq!(paths, vec![root.join("a.dat"), root.join("b.dat")]);
+        // files are sent once they are unchanged between two reads
+        assert_eq!(listen(&mut header_table), 0);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(listen(&mut header_table), 2);
+        // the header is sent once for the target
+        assert!(rx.recv().unwrap().update_header);
+        assert_eq!(rx.recv().unwrap().value, vec!["1,a\n"]);
+        assert_eq!(rx.recv().unwrap().value, vec!["2,b\n"]);
+        assert!(rx.try_recv().is_err());
+
+        assert!(root.join(DONE_DIR).join("a.dat").exists());
+        assert!(root.join(DONE_DIR).join("b.dat").exists());
+        assert!(root.join("c.txt").exists());
+        assert!(expand_path(&drop_pattern(&target)).is_empty());
+
+        // a file still being written is left,
+        // once it's stable the last line is sent even without newline
+        let path = root.join("a.dat");
+        append(&path, "header\n4,d\n");
+        assert_eq!(listen(&mut header_table), 0);
+        append(&path, "5,e\n6,f");
+        assert_eq!(listen(&mut header_table), 0);
+        assert!(path.exists());
+        assert_eq!(listen(&mut header_table), 3);
+        assert_eq!(rx.recv().unwrap().value, vec!["4,d\n", "5,e\n", "6,f"]);
+        assert!(rx.try_recv().is_err());
+
+        // same name is archived with a time suffix
+        assert_eq!(std::fs::read_dir(root.join(DONE_DIR)).unwrap().count(), 3);
+
+        // a file without rows sends nothing
+        append(&root.join("d.dat"), "header\n");
+        assert_eq!(listen(&mut header_table), 0);
+        assert_eq!(listen(&mut header_table), 0);
+        assert!(!root.join("d.dat").exists());
+        assert!(rx.try_recv().is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    error::Error,
+    io::{self, Read},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::{mpsc::SyncSender, Arc},
+    thread::{self, sleep, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    component::{
+        frame_cwb::{dispatch_frames, CwbFrameDecoder},
+        utils::{backoff::Backoff, shutdown::Shutdown},
+        MsgPayload,
+    },
+    config::NetworkConfig,
+};
+
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+const LOG_TARGET: &str = "network";
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+// retry until connected, None if shutdown
+fn connect(cfg: &NetworkConfig, shutdown: &Shutdown) -> Option<TcpStream> {
+    let mut backoff = Backoff::new(RECONNECT_INITIAL_DELAY, RECONNECT_MAX_DELAY);
+    while !shutdown.is_triggered() {
+        match TcpStream::connect(&cfg.address) {
+            Ok(stream) => {
+                log::info!(target: "info", "Connect success: {} - {}", cfg.name, cfg.address);
+                if let Err(e) = stream.set_read_timeout(Some(cfg.get_timeout())) {
+                    log::error!("Set read timeout failed: {e}");
+                }
+                return Some(stream);
+            }
+            Err(e) => log::error!("Connect failed: {e} - {} - {}", cfg.name, cfg.address),
+        }
+        shutdown.sleep(backoff.next_delay());
+    }
+    None
+}
+
+fn setup_tcp_client(
+    cfg: &NetworkConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    let cfg = cfg.clone();
+
+    let handle = thread::spawn(move || {
+        let mut counter = 0;
+        let mut buffer = [0; 1024];
+        let mut decoder =
+            CwbFrameDecoder::new(cfg.checksum.unwrap_or_default()).with_log_target(LOG_TARGET);
+        let mut stream = connect(&cfg, &shutdown);
+
+        while let Some(current) = stream.as_mut() {
+            if shutdown.is_triggered() {
+                break;
+            }
+
+            match current.read(&mut buffer) {
+                Ok(0) => {
+                    log::error!("Connection closed: {} - {}", cfg.name, cfg.address);
+                    decoder.reset();
+                    stream = connect(&cfg, &shutdown);
+                }
+                Ok(num) => {
+                    let data = &buffer[..num];
+                    log::info!(target: LOG_TARGET, "{} [{}] {:?}", cfg.name, num, data);
+                    counter += dispatch_frames(&mut decoder, data, cfg.get_tag(), &sender);
+                }
+                Err(ref e) if is_timeout(e) => (),
+                Err(e) => {
+                    log::error!("Connection lost: {e} - {} - {}", cfg.name, cfg.address);
+                    decoder.reset();
+                    shutdown.sleep(RECONNECT_INITIAL_DELAY);
+                    stream = connect(&cfg, &shutdown);
+                }
+            };
+        }
+        log::info!(target: "info", "Network reader stopped: {}", cfg.name);
+        counter
+    });
+
+    Ok(handle)
+}
+
+// a new connection replaces the current one, e.g. the converter reconnects after drop
+fn setup_tcp_server(
+    cfg: &NetworkConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    let listener = TcpListener::bind(&cfg.address)?;
+    listener.set_nonblocking(true)?;
+    let cfg = cfg.clone();
+
+    let handle = thread::spawn(move || {
+        let mut counter = 0;
+        let mut buffer = [0; 1024];
+        let mut decoder =
+            CwbFrameDecoder::new(cfg.checksum.unwrap_or_default()).with_log_target(LOG_TARGET);
+        let mut stream: Option<TcpStream> = None;
+
+        while !shutdown.is_triggered() {
+            match listener.accept() {
+                Ok((new_stream, addr)) => {
+                    log::info!(target: "info", "Accept connection: {} - {addr}", cfg.name);
+                    let ret = new_stream
+                        .set_nonblocking(false)
+                        .and_then(|_| new_stream.set_read_timeout(Some(ACCEPT_INTERVAL)));
+                    if let Err(e) = ret {
+                        log::error!("Setup connection failed: {e} - {} - {addr}", cfg.name);
+                        continue;
+                    }
+                    decoder.reset();
+                    stream = Some(new_stream);
+                }
+                Err(ref e) if is_timeout(e) => (),
+                Err(e) => log::error!("Accept failed: {e} - {}", cfg.name),
+            }
+
+            let Some(current) = stream.as_mut() else {
+                sleep(ACCEPT_INTERVAL);
+                continue;
+            };
+
+            match current.read(&mut buffer) {
+                Ok(0) => {
+                    log::error!("Connection closed: {}", cfg.name);
+                    decoder.reset();
+                    stream = None;
+                }
+                Ok(num) => {
+                    let data = &buffer[..num];
+                    log::info!(target: LOG_TARGET, "{} [{}] {:?}", cfg.name, num, data);
+                    counter += dispatch_frames(&mut decoder, data, cfg.get_tag(), &sender);
+                }
+                Err(ref e) if is_timeout(e) => (),
+                Err(e) => {
+                    log::error!("Connection lost: {e} - {}", cfg.name);
+                    decoder.reset();
+                    stream = None;
+                }
+            };
+        }
+        log::info!(target: "info", "Network reader stopped: {}", cfg.name);
+        counter
+    });
+
+    Ok(handle)
+}
+
+fn setup_udp(
+    cfg: &NetworkConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    let socket = UdpSocket::bind(&cfg.address)?;
+    socket.set_read_timeout(Some(cfg.get_timeout()))?;
+    let cfg = cfg.clone();
+
+    let handle = thread::spawn(move || {
+        let mut counter = 0;
+        let mut buffer = [0; 65536];
+        let mut decoder =
+            CwbFrameDecoder::new(cfg.checksum.unwrap_or_default()).with_log_target(LOG_TARGET);
+
+        while !shutdown.is_triggered() {
+            match socket.recv_from(&mut buffer) {
+                Ok((num, addr)) => {
+                    let data = &buffer[..num];
+                    log::info!(target: LOG_TARGET, "{} {addr} [{}] {:?}", cfg.name, num, data);
+                    counter += dispatch_frames(&mut decoder, data, cfg.get_tag(), &sender);
+                }
+                Err(ref e) if is_timeout(e) => (),
+                Err(e) => log::error!("{e} - {}", cfg.name),
+            };
+        }
+        log::info!(target: "info", "Network reader stopped: {}", cfg.name);
+        counter
+    });
+
+    Ok(handle)
+}
+
+// mode: [tcp_client, tcp_server, udp]
+pub fn setup_network_cwb(
+    cfg: &NetworkConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    match cfg.mode.as_str() {
+        "tcp_client" => setup_tcp_client(cfg, sender, shutdown),
+        "tcp_server" => setup_tcp_server(cfg, sender, shutdown),
+        "udp" => setup_udp(cfg, sender, shutdown),
+        val => Err(format!("Unsupport mode: {val:?}").into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Write, sync::mpsc};
+
+    use super::*;
+    use crate::component::frame_cwb::ChecksumType;
+
+    fn network_config(mode: &str, address: &str) -> NetworkConfig {
+        NetworkConfig {
+            name: format!("test_{mode}"),
+            tag: None,
+            mode: mode.to_string(),
+            address: address.to_string(),
+            timeout: Some(100),
+            checksum: Some(ChecksumType::None),
+        }
+    }
+
+    fn free_address() -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn tcp_client_reconnect() {
+        let (tx, rx) = mpsc::sync_channel(16);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let cfg = network_config("tcp_client", &address);
+        let _handle = setup_network_cwb(&cfg, tx, Shutdown::new()).unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .write_all(b"\x02,466920,MN,202501091555,\x03\r\n")
+            .unwrap();
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg.tag, "CWB");
+        assert_eq!(msg.dkind, "MN");
+
+        // drop connection, reader should connect again
+        drop(stream);
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .write_all(b"\x02,466920,HR,202501091600,\x03\r\n")
+            .unwrap();
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg.dkind, "HR");
+    }
+
+    #[test]
+    fn tcp_server_accept() {
+        let (tx, rx) = mpsc::sync_channel(16);
+        let address = free_address();
+        let cfg = network_config("tcp_server", &address);
+        let _handle = setup_network_cwb(&cfg, tx, Shutdown::new()).unwrap();
+
+        for dkind in ["MN", "HR"] {
+            let mut stream = TcpStream::connect(&address).unwrap();
+            let frame = format!("\x02,466920,{dkind},202501091555,\x03\r\n");
+            stream.write_all(frame.as_bytes()).unwrap();
+            let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(msg.dkind, dkind);
+        }
+    }
+
+    #[test]
+    fn udp_datagram() {
+        let (tx, rx) = mpsc::sync_channel(16);
+        let address = free_address();
+        let shutdown = Shutdown::new();
+        let cfg = network_config("udp", &address);
+        let handle = setup_network_cwb(&cfg, tx, shutdown.clone()).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .send_to(b"\x02,466920,SM,202501091555,\x03", &address)
+            .unwrap();
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg.dkind, "SM");
+
+        // reader stops within read timeout
+        shutdown.trigger();
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+}
@@ -1,12 +1,97 @@
 use std::{
     error::Error,
-    io::{self, BufRead, BufReader},
-    sync::{mpsc::Sender, Arc},
+    io::{self, BufRead, BufReader, Read},
+    sync::{mpsc::SyncSender, Arc},
     thread::{self, JoinHandle},
     time::Duration,
 };
 
-use crate::component::{parser_cwb::get_dkind, MsgPayload};
+use serialport::SerialPort;
+
+use crate::{
+    component::{
+        frame_cwb::{dispatch_frames, CwbFrameDecoder},
+        utils::{backoff::Backoff, shutdown::Shutdown},
+        MsgPayload,
+    },
+    config::SerialPortConfig,
+};
+
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+fn open_serial_port(
+    cfg: &SerialPortConfig,
+) -> Result<Box<dyn SerialPort>, Box<dyn Error + 'static>> {
+    let mut uart = cfg.builder()?.open()?;
+
+    // assert control lines on open
+    if let Some(dtr) = cfg.dtr {
+        uart.write_data_terminal_ready(dtr)?;
+    }
+    if let Some(rts) = cfg.rts {
+        uart.write_request_to_send(rts)?;
+    }
+    Ok(uart)
+}
+
+// device is gone, e.g. usb adapter unplugged
+pub fn is_disconnected(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    const DEVICE_GONE: [i32; 3] = [
+        5,  // EIO
+        6,  // ENXIO
+        19, // ENODEV
+    ];
+    #[cfg(windows)]
+    const DEVICE_GONE: [i32; 3] = [
+        31,   // ERROR_GEN_FAILURE
+        995,  // ERROR_OPERATION_ABORTED
+        1167, // ERROR_DEVICE_NOT_CONNECTED
+    ];
+
+    match e.kind() {
+        io::ErrorKind::BrokenPipe
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted => true,
+        _ => e
+            .raw_os_error()
+            .is_some_and(|code| DEVICE_GONE.contains(&code)),
+    }
+}
+
+// retry until the port is opened again, None if shutdown
+fn reconnect_serial_port(
+    cfg: &SerialPortConfig,
+    shutdown: &Shutdown,
+) -> Option<Box<dyn SerialPort>> {
+    let mut backoff = Backoff::new(RECONNECT_INITIAL_DELAY, RECONNECT_MAX_DELAY);
+    loop {
+        if !shutdown.sleep(backoff.next_delay()) {
+            return None;
+        }
+        match open_serial_port(cfg) {
+            Ok(uart) => {
+                log::info!(target: "info", "Reconnect serial port success: {}", cfg.path);
+                return Some(uart);
+            }
+            Err(e) => log::error!("Reconnect serial port failed: {e} - {}", cfg.path),
+        }
+    }
+}
+
+// open in the reader thread, a missing adapter is retried like a disconnect
+fn connect_serial_port(cfg: &SerialPortConfig, shutdown: &Shutdown) -> Option<Box<dyn SerialPort>> {
+    match open_serial_port(cfg) {
+        Ok(uart) => Some(uart),
+        Err(e) => {
+            log::error!("Open serial port failed: {e} - {}", cfg.path);
+            reconnect_serial_port(cfg, shutdown)
+        }
+    }
+}
 
 // pub fn setup_serial_port(
 //     path: &str,
@@ -43,109 +128,214 @@ use crate::component::{parser_cwb::get_dkind, MsgPayload};
 //     Ok(handle)
 // }
 
-// This function is better than `setup_serial_port_cwb()` which can check crc value
-#[allow(dead_code)]
+// Read line by line, the checksum after ETX is verified by `CwbFrameDecoder`
 pub fn setup_serial_port_cwb_by_line(
-    path: &str,
-    baudrate: u32,
-    sender: Sender<MsgPayload>,
+    cfg: &SerialPortConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
 ) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
-    let uart = serialport::new(path, baudrate)
-        .timeout(Duration::from_millis(100))
-        .open()
-        .expect("Open serial port failed");
+    cfg.builder()?;
+    let cfg = cfg.clone();
 
     let handle = thread::spawn(move || {
-        let mut buffer = String::new();
+        let mut counter = 0;
+        let mut buffer = Vec::new();
+        let Some(uart) = connect_serial_port(&cfg, &shutdown) else {
+            return counter;
+        };
         let mut reader = BufReader::new(uart);
+        let mut decoder = CwbFrameDecoder::new(cfg.checksum.unwrap_or_default());
 
-        loop {
+        while !shutdown.is_triggered() {
             buffer.clear();
-            match reader.read_line(&mut buffer) {
+            let disconnected = match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) => {
+                    log::error!("Serial port closed: {}", cfg.path);
+                    true
+                }
                 Ok(num) => {
-                    log::info!(target: "serialport", "[{}] {:?}", num, buffer);
-                    log::info!(target: "console", "[{}] {:?}", num, buffer);
-                    let Some(etx_idx) = buffer.find('\u{3}') else {
-                        log::warn!("Invalid data: {buffer:?}");
-                        continue;
-                    };
-                    let (msg, checksum) = buffer.split_at_mut(etx_idx + 1);
-
-                    if let Some(_stx_idx) = checksum.find('\u{2}') {
-                        log::error!("Invalid data: {buffer:?}");
-                        continue;
-                    }
-
-                    let Some(dkind) = get_dkind(msg) else {
-                        log::error!("Invalid data: {msg:?}");
-                        continue;
-                    };
-
-                    let msg = vec![msg.to_string()];
-                    let payload = MsgPayload::new("CWB", &dkind, msg);
-                    if let Err(e) = sender.send(payload) {
-                        log::error!("{e}");
-                    }
+                    log::info!(target: "serialport", "{} [{}] {:?}", cfg.get_name(), num, buffer);
+                    counter += dispatch_frames(&mut decoder, &buffer, cfg.get_tag(), &sender);
+                    false
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => false,
+                Err(e) if is_disconnected(&e) => {
+                    log::error!("Serial port disconnected: {e} - {}", cfg.path);
+                    true
+                }
+                Err(e) => {
+                    log::error!("{e}");
+                    false
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-                Err(e) => log::error!("{e}"),
             };
-            // println!("sleep...");
-            // sleep(Duration::from_millis(50));
+
+            if disconnected {
+                decoder.reset();
+                let Some(uart) = reconnect_serial_port(&cfg, &shutdown) else {
+                    break;
+                };
+                reader = BufReader::new(uart);
+            }
         }
+        log::info!(target: "info", "Serial port reader stopped: {}", cfg.get_name());
+        counter
     });
 
     Ok(handle)
 }
 
 pub fn setup_serial_port_cwb(
-    path: &str,
-    baudrate: u32,
-    sender: Sender<Arc<MsgPayload>>,
+    cfg: &SerialPortConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
 ) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
-    let uart = serialport::new(path, baudrate)
-        .timeout(Duration::from_millis(100))
-        .open()
-        .expect("Open serial port failed");
+    cfg.builder()?;
+    let cfg = cfg.clone();
 
     let handle = thread::spawn(move || {
-        let mut buffer = Vec::new();
-        let mut reader = BufReader::new(uart);
+        let mut counter = 0;
+        let mut buffer = [0; 1024];
+        let Some(mut uart) = connect_serial_port(&cfg, &shutdown) else {
+            return counter;
+        };
+        let mut decoder = CwbFrameDecoder::new(cfg.checksum.unwrap_or_default());
 
-        loop {
-            buffer.clear();
-            match reader.read_until(0x3, &mut buffer) {
-                // match reader.read_until(0x10, &mut buffer) {
+        while !shutdown.is_triggered() {
+            let disconnected = match uart.read(&mut buffer) {
+                Ok(0) => {
+                    log::error!("Serial port closed: {}", cfg.path);
+                    true
+                }
                 Ok(num) => {
-                    log::info!(target: "serialport", "[{}] {:?}", num, buffer);
-                    let Some(stx_idx) = buffer.iter().position(|&ele| ele == 0x2) else {
-                        log::warn!("Invalid data: {buffer:?}");
-                        continue;
-                    };
-                    let (_, rawmsg) = buffer.split_at_mut(stx_idx);
-
-                    let msg = String::from_utf8_lossy(rawmsg).to_string();
-                    log::info!(target: "console", "[{}] {:?}", num, msg);
-                    log::info!(target: "serialport", "[{}] {:?}", num, msg);
-
-                    let Some(dkind) = get_dkind(&msg) else {
-                        log::error!("Invalid data: {msg:?}");
-                        continue;
-                    };
-
-                    let msg = vec![msg.to_string()];
-                    let payload = MsgPayload::new("CWB", &dkind, msg);
-                    if let Err(e) = sender.send(Arc::new(payload)) {
-                        log::error!("{e}");
-                    }
+                    let data = &buffer[..num];
+                    log::info!(target: "serialport", "{} [{}] {:?}", cfg.get_name(), num, data);
+                    counter += dispatch_frames(&mut decoder, data, cfg.get_tag(), &sender);
+                    false
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => false,
+                Err(e) if is_disconnected(&e) => {
+                    log::error!("Serial port disconnected: {e} - {}", cfg.path);
+                    true
+                }
+                Err(e) => {
+                    log::error!("{e}");
+                    false
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-                Err(e) => log::error!("{e}"),
             };
-            // println!("sleep...");
-            // sleep(Duration::from_millis(50));
+
+            if disconnected {
+                decoder.reset();
+                let Some(new_uart) = reconnect_serial_port(&cfg, &shutdown) else {
+                    break;
+                };
+                uart = new_uart;
+            }
         }
+        log::info!(target: "info", "Serial port reader stopped: {}", cfg.get_name());
+        counter
     });
 
     Ok(handle)
 }
+
+// framing: [stream, line], default: stream
+pub fn setup_serial_port(
+    cfg: &SerialPortConfig,
+    sender: SyncSender<Arc<MsgPayload>>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    match cfg.framing.as_deref() {
+        None | Some("stream") => setup_serial_port_cwb(cfg, sender, shutdown),
+        Some("line") => setup_serial_port_cwb_by_line(cfg, sender, shutdown),
+        Some(val) => Err(format!("Unsupport framing: {val:?}").into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Write,
+        process::{Child, Command},
+        sync::mpsc,
+        thread::sleep,
+    };
+
+    use crate::component::frame_cwb::ChecksumType;
+
+    use super::*;
+
+    const PORT_READER: &str = "/tmp/naho_ttyUSB0";
+    const PORT_WRITER: &str = "/tmp/naho_ttyUSB1";
+
+    // same as tests/setup_loopback_serial_port.sh
+    fn setup_loopback() -> Child {
+        let child = Command::new("socat")
+            .arg(format!("pty,link={PORT_READER},raw,echo=0"))
+            .arg(format!("pty,link={PORT_WRITER},raw,echo=0"))
+            .spawn()
+            .expect("socat is required");
+        sleep(Duration::from_millis(500));
+        child
+    }
+
+    fn loopback_config(path: &str) -> SerialPortConfig {
+        SerialPortConfig {
+            name: None,
+            tag: None,
+            framing: None,
+            path: path.to_string(),
+            baudrate: 9600,
+            data_bits: Some(7),
+            parity: Some("even".to_string()),
+            stop_bits: Some(1),
+            flow_control: None,
+            timeout: None,
+            dtr: Some(true),
+            rts: None,
+            checksum: Some(ChecksumType::None),
+        }
+    }
+
+    fn write_frame(data: &str) {
+        let mut uart = open_serial_port(&loopback_config(PORT_WRITER)).unwrap();
+        uart.write_all(format!("\u{2}{data}\u{3}\r\n").as_bytes())
+            .unwrap();
+        uart.flush().unwrap();
+    }
+
+    #[test]
+    fn device_gone() {
+        assert!(is_disconnected(&io::Error::from(io::ErrorKind::BrokenPipe)));
+        assert!(is_disconnected(&io::Error::from_raw_os_error(19)));
+        assert!(!is_disconnected(&io::Error::from(io::ErrorKind::TimedOut)));
+    }
+
+    #[test]
+    #[ignore = "requires socat"]
+    fn reconnect_after_disconnect() {
+        let (tx, rx) = mpsc::sync_channel(16);
+        let mut loopback = setup_loopback();
+        let shutdown = Shutdown::new();
+        let handle =
+            setup_serial_port_cwb(&loopback_config(PORT_READER), tx, shutdown.clone()).unwrap();
+
+        write_frame(",466920,MN,202501091555,");
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg.dkind, "MN");
+
+        // unplug and plug again
+        loopback.kill().unwrap();
+        loopback.wait().unwrap();
+        let mut loopback = setup_loopback();
+        sleep(RECONNECT_INITIAL_DELAY * 4);
+
+        write_frame(",466920,HR,202501091600,");
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(msg.dkind, "HR");
+
+        shutdown.trigger();
+        assert_eq!(handle.join().unwrap(), 2);
+        loopback.kill().unwrap();
+        loopback.wait().unwrap();
+    }
+}
@@ -0,0 +1,459 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS, RecvTimeoutError};
+use serde_json::{Map, Value};
+
+use crate::config::{MqttConfig, SinkConfig, SystemConfig};
+
+use super::{
+    codec::CodecConfigMetadata,
+    dispatcher::Sink,
+    parser_toa5::Toa5Header,
+    receiver_raw::gen_headertable_key,
+    receiver_sqlite::{parse_payload, to_json_value},
+    utils::backoff::Backoff,
+    MsgPayload,
+};
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_OFFLINE_CAPACITY: usize = 10000;
+const DEFAULT_STATION: &str = "unknown";
+// requests buffered by the client before they go to the offline queue
+const REQUEST_CAPACITY: usize = 100;
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const DROP_REPORT_INTERVAL: usize = 1000;
+
+// formation names (sqlite3) as keys
+fn gen_record(values: Vec<sqlite::Value>, config: &CodecConfigMetadata) -> Map<String, Value> {
+    config
+        .formation
+        .iter()
+        .zip(values)
+        .map(|(formation, value)| (formation.sqlite3.name.clone(), to_json_value(value)))
+        .collect()
+}
+
+fn gen_topic(template: &str, msg: &MsgPayload, name: &str, station: &str) -> String {
+    template
+        .replace("{tag}", &msg.tag)
+        .replace("{dkind}", &msg.dkind)
+        .replace("{name}", name)
+        .replace("{station}", station)
+}
+
+// keep the client connected, the connection state is shared with the publisher
+fn poll_connection(
+    name: String,
+    mut connection: rumqttc::Connection,
+    connected: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut backoff = Backoff::new(RECONNECT_INITIAL_DELAY, RECONNECT_MAX_DELAY);
+    while !stop.load(Ordering::SeqCst) {
+        match connection.recv_timeout(POLL_TIMEOUT) {
+            Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
+                connected.store(true, Ordering::SeqCst);
+                backoff = Backoff::new(RECONNECT_INITIAL_DELAY, RECONNECT_MAX_DELAY);
+                log::info!(target: "info", "MQTT {name} connected.");
+            }
+            Ok(Ok(Event::Outgoing(Outgoing::Disconnect))) => break,
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => {
+                if connected.swap(false, Ordering::SeqCst) {
+                    log::warn!("MQTT {name} disconnected: {e}");
+                }
+                let deadline = Instant::now() + backoff.next_delay();
+                while Instant::now() < deadline && !stop.load(Ordering::SeqCst) {
+                    thread::sleep(POLL_TIMEOUT.min(deadline - Instant::now()));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    connected.store(false, Ordering::SeqCst);
+}
+
+// publish each record as json, records are kept in the offline queue while the broker is
+// unreachable, the oldest ones are dropped when it is full
+pub struct MqttPublisher {
+    name: String,
+    config: Arc<SystemConfig>,
+    cfg: MqttConfig,
+    qos: QoS,
+    client: Option<Client>,
+    handler: Option<JoinHandle<()>>,
+    connected: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    offline: VecDeque<(String, String)>,
+    dropped: usize,
+    // header key -> station of the TOA5 header
+    stations: HashMap<String, String>,
+}
+
+impl MqttPublisher {
+    pub fn new(
+        sink: &SinkConfig,
+        config: Arc<SystemConfig>,
+    ) -> Result<Self, Box<dyn Error + 'static>> {
+        let cfg = MqttConfig::from_sink(sink)?;
+        let qos = cfg.get_qos()?;
+        Ok(MqttPublisher {
+            name: sink.get_name().to_string(),
+            config,
+            cfg,
+            qos,
+            client: None,
+            handler: None,
+            connected: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+            offline: VecDeque::new(),
+            dropped: 0,
+            stations: HashMap::new(),
+        })
+    }
+
+    fn get_station(&self, msg: &MsgPayload, record: &Map<String, Value>) -> String {
+        let field = self
+            .cfg
+            .station_field
+            .as_ref()
+            .and_then(|name| record.get(name))
+            .filter(|val| !val.is_null());
+        match field {
+            Some(Value::String(val)) => val.clone(),
+            Some(val) => val.to_string(),
+            None => self
+                .stations
+                .get(&gen_headertable_key(msg))
+                .or(self.cfg.station.as_ref())
+                .cloned()
+                .unwrap_or(DEFAULT_STATION.to_string()),
+        }
+    }
+
+    fn enqueue(&mut self, topic: String, payload: String) {
+        let capacity = self
+            .cfg
+            .offline_capacity
+            .unwrap_or(DEFAULT_OFFLINE_CAPACITY);
+        if self.offline.len() >= capacity {
+            self.offline.pop_front();
+            self.dropped += 1;
+            if self.dropped % DROP_REPORT_INTERVAL == 1 {
+                log::warn!(
+                    "MQTT {} offline queue is full, dropped: {}",
+                    self.name,
+                    self.dropped
+                );
+            }
+        }
+        self.offline.push_back((topic, payload));
+    }
+
+    // send queued records first to keep order
+    fn flush(&mut self) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        let retain = self.cfg.retain.unwrap_or(true);
+        while self.connected.load(Ordering::SeqCst) {
+            let Some((topic, payload)) = self.offline.pop_front() else {
+                break;
+            };
+            if client
+                .try_publish(&topic, self.qos, retain, payload.as_bytes())
+                .is_err()
+            {
+                self.offline.push_front((topic, payload));
+                break;
+            }
+        }
+    }
+
+    fn publish(&mut self, topic: String, payload: String) {
+        self.flush();
+        if !self.offline.is_empty() || !self.connected.load(Ordering::SeqCst) {
+            self.enqueue(topic, payload);
+            return;
+        }
+
+        let retain = self.cfg.retain.unwrap_or(true);
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        if client
+            .try_publish(&topic, self.qos, retain, payload.as_bytes())
+            .is_err()
+        {
+            self.enqueue(topic, payload);
+        }
+    }
+}
+
+impl Sink for MqttPublisher {
+    fn setup(&mut self) -> Result<(), Box<dyn Error + 'static>> {
+        let client_id = match self.cfg.client_id.as_ref() {
+            Some(val) => val.clone(),
+            None => format!("naho_data_receiver_{}", self.name),
+        };
+        let port = self.cfg.port.unwrap_or(DEFAULT_PORT);
+        let mut options = MqttOptions::new(client_id, &self.cfg.host, port);
+        options.set_keep_alive(self.cfg.get_keep_alive());
+        // broker keeps the session, inflight messages are sent again after reconnect
+        options.set_clean_session(self.qos == QoS::AtMostOnce);
+        if let (Some(username), Some(password)) = (&self.cfg.username, &self.cfg.password) {
+            options.set_credentials(username, password);
+        }
+
+        let (client, connection) = Client::new(options, REQUEST_CAPACITY);
+        let name = self.name.clone();
+        let connected = self.connected.clone();
+        let stop = self.stop.clone();
+        let handler = thread::Builder::new()
+            .name(format!("{}_connection", self.name))
+            .spawn(move || poll_connection(name, connection, connected, stop))?;
+
+        self.client = Some(client);
+        self.handler = Some(handler);
+        Ok(())
+    }
+
+    fn handle(&mut self, msg: Arc<MsgPayload>) {
+        if msg.update_header {
+            if let Some(header) = Toa5Header::parse(&msg.value) {
+                self.stations
+                    .insert(gen_headertable_key(&msg), header.station);
+            }
+            return;
+        }
+
+        let config = self.config.clone();
+        let Some(dconfig) = config
+            .codec
+            .get(&msg.tag)
+            .and_then(|cfg| cfg.get_data_config(&msg.dkind))
+        else {
+            log::error!("Unsupport data: {:?} {:?}", msg.tag, msg.dkind);
+            return;
+        };
+
+        for (idx, value) in msg.value.iter().enumerate() {
+            let (Some(values), _) = parse_payload(&msg, idx, dconfig) else {
+                log::error!("Invalid: {value:?}");
+                continue;
+            };
+            let record = gen_record(values, dconfig);
+            let station = self.get_station(&msg, &record);
+            let topic = gen_topic(self.cfg.get_topic(), &msg, &dconfig.name, &station);
+            self.publish(topic, Value::Object(record).to_string());
+        }
+    }
+
+    fn shutdown(&mut self) {
+        // give the broker a chance to receive the queued records
+        let deadline = Instant::now() + DISCONNECT_TIMEOUT;
+        while !self.offline.is_empty()
+            && self.connected.load(Ordering::SeqCst)
+            && Instant::now() < deadline
+        {
+            self.flush();
+            thread::sleep(Duration::from_millis(10));
+        }
+        if !self.offline.is_empty() {
+            log::warn!(
+                "MQTT {} dropped {} offline records.",
+                self.name,
+                self.offline.len()
+            );
+        }
+
+        if let Some(client) = self.client.take() {
+            let _ = client.try_disconnect();
+        }
+        if let Some(handler) = self.handler.take() {
+            while !handler.is_finished()
+                && self.connected.load(Ordering::SeqCst)
+                && Instant::now() < deadline
+            {
+                thread::sleep(Duration::from_millis(10));
+            }
+            self.stop.store(true, Ordering::SeqCst);
+            let _ = handler.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn config() -> Arc<SystemConfig> {
+        let config = serde_json::json!({
+            "global": {"log4rs_cfg": ""},
+            "codec": {"CWB": {"tag": "CWB", "metadatas": [{
+                "name": "data",
+                "dkind": ["MN"],
+                "formation": [
+                    {
+                        "spec": {"name": "stno", "description": "", "dtype": "text"},
+                        "rust": {"name": "stno", "dtype": "text"},
+                        "sqlite3": {"name": "stno", "dtype": "TEXT"}
+                    },
+                    {
+                        "spec": {"name": "temp", "description": "", "dtype": "float"},
+                        "rust": {"name": "temp", "dtype": "float"},
+                        "sqlite3": {"name": "temp", "dtype": "REAL"}
+                    }
+                ]
+            }]}}
+        });
+        Arc::new(serde_json::from_value(config).unwrap())
+    }
+
+    fn publisher(port: u16, options: serde_json::Value) -> MqttPublisher {
+        let mut cfg = SinkConfig::new("mqtt");
+        let mut options = options;
+        options["host"] = "127.0.0.1".into();
+        options["port"] = port.into();
+        cfg.options = Some(options);
+        MqttPublisher::new(&cfg, config()).unwrap()
+    }
+
+    #[test]
+    fn topic_and_record() {
+        let mut publisher = publisher(DEFAULT_PORT, serde_json::json!({"station_field": "stno"}));
+        let msg = MsgPayload::new("CWB", "MN", vec!["466920,21.5".to_string()]);
+        let dconfig = publisher.config.codec["CWB"].get_data_config("MN").unwrap();
+        let (Some(values), _) = parse_payload(&msg, 0, dconfig) else {
+            panic!("parse failed");
+        };
+        let record = gen_record(values, dconfig);
+        assert_eq!(
+            Value::Object(record.clone()).to_string(),
+            r#"{"stno":"466920","temp":21.5}"#
+        );
+
+        let station = publisher.get_station(&msg, &record);
+        let topic = gen_topic(publisher.cfg.get_topic(), &msg, "data", &station);
+        assert_eq!(topic, "naho/CWB/MN/466920");
+
+        // station of the TOA5 header is used without `station_field`
+        publisher.cfg.station_field = None;
+        publisher
+            .stations
+            .insert(gen_headertable_key(&msg), "NAHO".to_string());
+        assert_eq!(publisher.get_station(&msg, &record), "NAHO");
+    }
+
+    #[test]
+    fn offline_queue() {
+        // nothing is listening on this port
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut publisher = publisher(port, serde_json::json!({"offline_capacity": 2}));
+        publisher.setup().unwrap();
+        for idx in 0..3 {
+            let msg = MsgPayload::new("CWB", "MN", vec![format!("466920,{idx}")]);
+            publisher.handle(Arc::new(msg));
+        }
+        assert_eq!(publisher.offline.len(), 2);
+        assert_eq!(publisher.dropped, 1);
+        assert!(publisher.offline[0].1.contains("\"temp\":1"));
+        publisher.shutdown();
+    }
+
+    // minimal MQTT 3.1.1 broker, return (topic, payload) of received PUBLISH packets
+    fn mock_broker(listener: TcpListener) -> JoinHandle<Vec<(String, String)>> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut result = Vec::new();
+            loop {
+                let mut head = [0; 1];
+                if stream.read_exact(&mut head).is_err() {
+                    break;
+                }
+                let (mut length, mut shift) = (0, 0);
+                loop {
+                    let mut byte = [0; 1];
+                    stream.read_exact(&mut byte).unwrap();
+                    length += ((byte[0] & 0x7f) as usize) << shift;
+                    shift += 7;
+                    if byte[0] & 0x80 == 0 {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).unwrap();
+
+                match head[0] >> 4 {
+                    1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap(),
+                    3 => {
+                        let qos = (head[0] >> 1) & 0x03;
+                        let size = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8(body[2..2 + size].to_vec()).unwrap();
+                        let mut offset = 2 + size;
+                        if qos > 0 {
+                            let id = &body[offset..offset + 2];
+                            stream.write_all(&[0x40, 0x02, id[0], id[1]]).unwrap();
+                            offset += 2;
+                        }
+                        let payload = String::from_utf8(body[offset..].to_vec()).unwrap();
+                        result.push((topic, payload));
+                    }
+                    12 => stream.write_all(&[0xd0, 0x00]).unwrap(),
+                    14 => break,
+                    _ => (),
+                }
+            }
+            result
+        })
+    }
+
+    #[test]
+    fn publish_to_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = mock_broker(listener);
+
+        let mut publisher = publisher(port, serde_json::json!({"station": "test"}));
+        publisher.setup().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !publisher.connected.load(Ordering::SeqCst) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let msg = MsgPayload::new("CWB", "MN", vec!["466920,21.5".to_string()]);
+        publisher.handle(Arc::new(msg));
+        assert!(publisher.offline.is_empty());
+        publisher.shutdown();
+
+        let result = broker.join().unwrap();
+        assert_eq!(
+            result,
+            vec![(
+                "naho/CWB/MN/test".to_string(),
+                r#"{"stno":"466920","temp":21.5}"#.to_string()
+            )]
+        );
+    }
+}
@@ -4,8 +4,7 @@ use std::{
     fs::{create_dir_all, File},
     io::Write,
     path::Path,
-    sync::{mpsc::Receiver, Arc},
-    thread::{self, JoinHandle},
+    sync::Arc,
 };
 
 use chrono::NaiveDateTime;
@@ -13,6 +12,7 @@ use chrono::NaiveDateTime;
 use crate::{component::utils::files::backup_file, config::SystemConfig};
 
 use super::{
+    dispatcher::Sink,
     utils::files::{cal_hash, generate_db_filepath, HeaderTable, HeaderTableValue},
     MsgPayload, DTAETIME_FMT,
 };
@@ -21,10 +21,12 @@ pub fn gen_headertable_key(msg: &MsgPayload) -> String {
     format!("{}_{}", msg.tag, msg.dkind)
 }
 
+// the header of `msg` is written if `with_header`, a changed header backs up the file
 fn create_file_with_header<P>(
     path: P,
     msg: &MsgPayload,
     table: &mut HeaderTable,
+    with_header: bool,
 ) -> Result<(), Box<dyn Error + 'static>>
 where
     P: AsRef<Path>,
@@ -47,146 +49,195 @@ where
         Ok(())
     }
 
-    match (msg.tag.as_str(), msg.dkind.as_str()) {
-        ("NAHO", _dkind) => {
-            let key = gen_headertable_key(msg);
-            let Some(tval) = table.get_mut(&key) else {
-                return Err(String::from("Can't find header.").into());
-            };
-
-            if !path.as_ref().exists() {
-                _create_file_with_header(&path, &tval.header)?;
-            } else if tval.is_update {
-                backup_file(&path)?;
-                _create_file_with_header(&path, &tval.header)?;
-            }
-            tval.is_update = false;
-        }
-        _ => {
-            if !path.as_ref().exists() {
-                _create_file_with_header(path, &Vec::new())?;
-            }
+    if !with_header {
+        if !path.as_ref().exists() {
+            _create_file_with_header(path, &Vec::new())?;
         }
+        return Ok(());
     }
+
+    let key = gen_headertable_key(msg);
+    let Some(tval) = table.get_mut(&key) else {
+        return Err(String::from("Can't find header.").into());
+    };
+
+    if !path.as_ref().exists() {
+        _create_file_with_header(&path, &tval.header)?;
+    } else if tval.is_update {
+        backup_file(&path)?;
+        _create_file_with_header(&path, &tval.header)?;
+    }
+    tval.is_update = false;
     Ok(())
 }
 
-pub fn setup_rawdata_recorder(
-    receiver: Receiver<Arc<MsgPayload>>,
+pub struct RawdataRecorder {
     config: Arc<SystemConfig>,
-) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
-    for (_key, val) in config.codec.iter() {
-        if let Some(cfg) = val.rawdata.as_ref() {
-            create_dir_all(&cfg.directory)?;
+    header_table: HeaderTable,
+}
+
+impl RawdataRecorder {
+    pub fn new(config: Arc<SystemConfig>) -> Self {
+        RawdataRecorder {
+            config,
+            header_table: HeaderTable::new(),
         }
     }
+}
 
-    let handler = thread::spawn(move || {
-        let mut header_table = HeaderTable::new();
-
-        loop {
-            while let Ok(msg) = receiver.recv() {
-                // for header msg
-                if msg.update_header {
-                    let new_hash = cal_hash(&msg.value);
-
-                    let key = gen_headertable_key(&msg);
-                    header_table
-                        .entry(key)
-                        .and_modify(|tval| {
-                            if tval.hash != new_hash {
-                                tval.header = msg.value.clone();
-                                tval.hash = new_hash;
-                                tval.is_update = true;
-                            }
-                        })
-                        .or_insert(HeaderTableValue {
-                            hash: new_hash,
-                            header: msg.value.clone(),
-                            is_update: true,
-                        });
+impl Sink for RawdataRecorder {
+    fn setup(&mut self) -> Result<(), Box<dyn Error + 'static>> {
+        for (_key, val) in self.config.codec.iter() {
+            if let Some(cfg) = val.rawdata.as_ref() {
+                create_dir_all(&cfg.directory)?;
+            }
+        }
+        Ok(())
+    }
 
-                    continue;
-                }
+    fn handle(&mut self, msg: Arc<MsgPayload>) {
+        // for header msg
+        if msg.update_header {
+            let new_hash = cal_hash(&msg.value);
+
+            let key = gen_headertable_key(&msg);
+            self.header_table
+                .entry(key)
+                .and_modify(|tval| {
+                    if tval.hash != new_hash {
+                        tval.header = msg.value.clone();
+                        tval.hash = new_hash;
+                        tval.is_update = true;
+                    }
+                })
+                .or_insert(HeaderTableValue {
+                    hash: new_hash,
+                    header: msg.value.clone(),
+                    is_update: true,
+                });
+
+            return;
+        }
 
-                // for data msg
-                let Some(cfg) = config.codec.get(&msg.tag) else {
-                    log::error!("Unsupport tag: {:?}", msg.tag);
-                    continue;
-                };
+        // for data msg
+        let Some(cfg) = self.config.codec.get(&msg.tag) else {
+            log::error!("Unsupport tag: {:?}", msg.tag);
+            return;
+        };
+
+        let Some(cfg_rawdata) = cfg.rawdata.as_ref() else {
+            log::info!("Unsupport record rawdata: {:?}", msg.tag);
+            return;
+        };
+
+        let Some(dconfig) = cfg.get_data_config(&msg.dkind) else {
+            log::error!("Invalid: {msg:?}");
+            return;
+        };
+
+        let Some(datetime_info) = dconfig.get_datetime_info() else {
+            log::error!(
+                "Unsupport data format. tag:{:?}; dkind:{:?}",
+                &msg.tag,
+                &msg.dkind
+            );
+            return;
+        };
+
+        for value in msg.value.iter() {
+            let mut words = value.split(',');
+
+            let offset = if dconfig.stx_etx == Some(true) {
+                datetime_info.0 + 1
+            } else {
+                datetime_info.0
+            };
+            let Some(timestr) = words.nth(offset) else {
+                log::error!("Invalid: {value:?}");
+                continue;
+            };
 
-                let Some(cfg_rawdata) = cfg.rawdata.as_ref() else {
-                    log::info!("Unsupport record rawdata: {:?}", msg.tag);
-                    continue;
-                };
+            let Some(timefmt) = &datetime_info.1.rust.unit else {
+                log::error!(
+                    "Unsupport data format. tag:{:?}; dkind:{:?}",
+                    &msg.tag,
+                    &msg.dkind
+                );
+                continue;
+            };
 
-                let Some(dconfig) = cfg.get_data_config(&msg.dkind) else {
-                    log::error!("Invalid: {msg:?}");
-                    continue;
-                };
-
-                let Some(datetime_info) = dconfig.get_datetime_info() else {
-                    log::error!(
-                        "Unsupport data format. tag:{:?}; dkind:{:?}",
-                        &msg.tag,
-                        &msg.dkind
-                    );
-                    continue;
-                };
-
-                for value in msg.value.iter() {
-                    let mut words = value.split(',');
-
-                    let offset = if dconfig.stx_etx == Some(true) {
-                        datetime_info.0 + 1
-                    } else {
-                        datetime_info.0
-                    };
-                    let Some(timestr) = words.nth(offset) else {
-                        log::error!("Invalid: {value:?}");
-                        continue;
-                    };
-
-                    let Some(timefmt) = &datetime_info.1.rust.unit else {
-                        log::error!(
-                            "Unsupport data format. tag:{:?}; dkind:{:?}",
-                            &msg.tag,
-                            &msg.dkind
-                        );
-                        continue;
-                    };
-
-                    let Ok(time) = NaiveDateTime::parse_from_str(timestr, &timefmt) else {
-                        log::error!("Invalid: {value:?}");
-                        continue;
-                    };
-
-                    let mut opts = HashMap::new();
-                    opts.insert(
-                        "datetime".to_string(),
-                        time.format(DTAETIME_FMT).to_string(),
-                    );
-                    let filepath =
-                        generate_db_filepath(&cfg.tag, cfg_rawdata, dconfig, &opts).unwrap();
-
-                    if let Err(e) = create_file_with_header(&filepath, &msg, &mut header_table) {
-                        log::error!("System Error. {e}");
-                    }
+            let Ok(time) = NaiveDateTime::parse_from_str(timestr, &timefmt) else {
+                log::error!("Invalid: {value:?}");
+                continue;
+            };
 
-                    let mut file = match File::options().append(true).open(&filepath) {
-                        Ok(file) => file,
-                        Err(e) => {
-                            log::error!("System Error. {e}");
-                            continue;
-                        }
-                    };
+            let mut opts = HashMap::new();
+            opts.insert(
+                "datetime".to_string(),
+                time.format(DTAETIME_FMT).to_string(),
+            );
+            let Some(filepath) = generate_db_filepath(&cfg.tag, cfg_rawdata, dconfig, &opts) else {
+                log::error!("Generate rawdata path failed: {msg:?}");
+                continue;
+            };
 
-                    if let Err(e) = file.write(format!("{}\n", value.trim()).as_bytes()) {
-                        log::error!("System Error. {e}");
-                    }
+            // a value isn't written to a file without its header
+            if let Err(e) = create_file_with_header(
+                &filepath,
+                &msg,
+                &mut self.header_table,
+                self.config.get_rawdata_header(&msg.tag),
+            ) {
+                log::error!("System Error. {e}");
+                continue;
+            }
+
+            let mut file = match File::options().append(true).open(&filepath) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::error!("System Error. {e}");
+                    continue;
                 }
+            };
+
+            if let Err(e) = file.write(format!("{}\n", value.trim()).as_bytes()) {
+                log::error!("System Error. {e}");
             }
         }
-    });
-    Ok(handler)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rawdata_header() {
+        let root = std::env::temp_dir().join("naho_rawdata_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let header = vec!["\"TOA5\",\"VAISALA\"\n".to_string()];
+        let msg = MsgPayload::new("VAISALA", "Min", Vec::new());
+        let mut table = HeaderTable::new();
+
+        // header is required once it's enabled
+        let path = root.join("with_header.dat");
+        assert!(create_file_with_header(&path, &msg, &mut table, true).is_err());
+        table.insert(
+            gen_headertable_key(&msg),
+            HeaderTableValue {
+                hash: cal_hash(&header),
+                header: header.clone(),
+                is_update: true,
+            },
+        );
+        create_file_with_header(&path, &msg, &mut table, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), header.concat());
+        assert!(!table[&gen_headertable_key(&msg)].is_update);
+
+        let path = root.join("without_header.dat");
+        create_file_with_header(&path, &msg, &mut table, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
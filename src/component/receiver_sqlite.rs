@@ -1,22 +1,25 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     error::Error,
     fmt::Display,
     fs::create_dir_all,
-    path::Path,
-    sync::{mpsc::Receiver, Arc},
-    thread::{self, JoinHandle},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use crate::{
     component::{receiver_raw::gen_headertable_key, utils::files::backup_file},
     config::SystemConfig,
 };
-use chrono::{NaiveDateTime, NaiveTime};
+use chrono::{Local, NaiveDateTime, NaiveTime};
 use regex::Regex;
 
 use super::{
     codec::{CodecConfigBase, CodecConfigMetadata},
+    dispatcher::Sink,
+    parser_cwb::CwbRecord,
+    parser_sitest::{SensorHealth, HEALTH_COLUMNS},
+    parser_toa5::Toa5Header,
     utils::files::{generate_db_filepath, is_update_header, HeaderTable},
     MsgPayload, DTAETIME_FMT,
 };
@@ -68,6 +71,73 @@ where
     }
 }
 
+const SCHEMA_TABLE: &str = "schema_meta";
+// columns added by the recorder, not listed in `formation`
+const MANAGED_COLUMNS: [&str; 5] = [
+    "id",
+    "rawdata",
+    "missing_count",
+    "header_id",
+    "flag_uploaded",
+];
+// header versions, rows of data tables are linked by `header_id`
+const HEADERS_COLUMNS: [(&str, &str); 13] = [
+    ("tablename", "TEXT"),
+    ("header", "TEXT"),
+    ("created_at", "TEXT"),
+    ("station", "TEXT"),
+    ("logger_model", "TEXT"),
+    ("logger_serial", "TEXT"),
+    ("os_version", "TEXT"),
+    ("program", "TEXT"),
+    ("program_signature", "TEXT"),
+    ("logger_table", "TEXT"),
+    ("fields", "TEXT"),
+    ("units", "TEXT"),
+    ("processing", "TEXT"),
+];
+
+fn create_headers_table(connection: &sqlite::Connection) -> Result<(), sqlite::Error> {
+    let mut tableinfo = String::new();
+    tableinfo.push_str("id INTEGER PRIMARY KEY AUTOINCREMENT");
+    for (name, dtype) in HEADERS_COLUMNS.iter() {
+        tableinfo.push_str(&format!(", {name} {dtype}"));
+    }
+
+    let statement = format!("CREATE TABLE IF NOT EXISTS headers ({tableinfo});");
+    connection.execute(statement)
+}
+
+fn create_schema_table(connection: &sqlite::Connection) -> Result<(), sqlite::Error> {
+    let statement =
+        format!("CREATE TABLE IF NOT EXISTS {SCHEMA_TABLE} (key TEXT PRIMARY KEY, value TEXT);");
+    connection.execute(statement)
+}
+
+fn get_schema_version(connection: &sqlite::Connection) -> i64 {
+    let query = format!("SELECT value FROM {SCHEMA_TABLE} WHERE key = 'version';");
+    let Ok(mut statement) = connection.prepare(query) else {
+        return 0;
+    };
+    match statement.next() {
+        Ok(sqlite::State::Row) => statement
+            .read::<String, _>("value")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn set_schema_version(connection: &sqlite::Connection, version: i64) -> Result<(), sqlite::Error> {
+    let query =
+        format!("INSERT OR REPLACE INTO {SCHEMA_TABLE} (key, value) VALUES ('version', ?);");
+    let mut statement = connection.prepare(query)?;
+    statement.bind((1, version.to_string().as_str()))?;
+    statement.next()?;
+    Ok(())
+}
+
 fn create_db<P>(path: P, config: &CodecConfigBase) -> Result<(), Box<dyn Error + 'static>>
 where
     P: AsRef<Path>,
@@ -81,49 +151,172 @@ where
         }
     }
 
-    let mut tableinfo = String::new();
-    tableinfo.push_str("id INTEGER PRIMARY KEY AUTOINCREMENT");
-    tableinfo.push_str(", tablename TEXT");
-    tableinfo.push_str(", header TEXT");
-
-    let statement = format!("CREATE TABLE IF NOT EXISTS headers ({tableinfo});");
-    connection.execute(statement)?;
+    create_headers_table(&connection)?;
+    create_schema_table(&connection)?;
+    set_schema_version(&connection, 1)?;
     Ok(())
 }
 
-fn check_column<P>(path: P, config: &CodecConfigBase) -> Result<(), SQLiteErrorType>
+// column name -> declared type
+fn get_table_info(
+    connection: &sqlite::Connection,
+    table: &str,
+) -> Result<HashMap<String, String>, SQLiteErrorType> {
+    let mut result = HashMap::new();
+    let query = format!("PRAGMA table_info({table});");
+    let Ok(mut statement) = connection.prepare(query) else {
+        return Err(SQLiteErrorType::Invalid);
+    };
+    while let Ok(sqlite::State::Row) = statement.next() {
+        let (Ok(name), Ok(dtype)) = (
+            statement.read::<String, _>("name"),
+            statement.read::<String, _>("type"),
+        ) else {
+            return Err(SQLiteErrorType::Invalid);
+        };
+        result.insert(name, dtype);
+    }
+    Ok(result)
+}
+
+// Add missing tables and columns, return true if the database is changed.
+// A changed column type or a removed column is `NotMatch`, the file should be backup.
+fn migrate_db<P>(path: P, config: &CodecConfigBase) -> Result<bool, SQLiteErrorType>
 where
     P: AsRef<Path>,
 {
-    let Ok(connection) = sqlite::open(path) else {
+    let Ok(connection) = sqlite::open(&path) else {
         return Err(SQLiteErrorType::Invalid);
     };
+
+    let mut statements = Vec::new();
     for dconfig in config.metadatas.iter() {
-        let mut set = HashSet::new();
-        let query = format!("PRAGMA table_info({});", dconfig.name);
-        let Ok(mut statement) = connection.prepare(query) else {
-            return Err(SQLiteErrorType::Invalid);
-        };
-        while let Ok(sqlite::State::Row) = statement.next() {
-            if let Ok(name) = statement.read::<String, _>("name") {
-                set.insert(name);
+        let columns = get_table_info(&connection, &dconfig.name)?;
+        if columns.is_empty() {
+            let statement = dconfig
+                .dkind
+                .first()
+                .and_then(|dkind| config.gen_sqlite3_create_table_cmd(dkind, &dconfig.name));
+            statements.extend(statement);
+            continue;
+        }
+
+        let mut expected = dconfig
+            .formation
+            .iter()
+            .map(|mem| (mem.sqlite3.name.as_str(), mem.sqlite3.dtype.as_str()))
+            .collect::<Vec<_>>();
+        if dconfig.raw_save == Some(true) {
+            expected.push(("rawdata", "TEXT"));
+        }
+        if dconfig.sitest.is_some() {
+            expected.extend(HEALTH_COLUMNS.iter().map(|name| (*name, "INTEGER")));
+        }
+        if !columns.contains_key("missing_count") {
+            expected.push(("missing_count", "INTEGER"));
+        }
+        if !columns.contains_key("header_id") {
+            expected.push(("header_id", "INTEGER"));
+        }
+        if !columns.contains_key("flag_uploaded") {
+            expected.push(("flag_uploaded", "BOOLEAN DEFAULT FALSE"));
+        }
+
+        for (name, dtype) in expected.iter() {
+            match columns.get(*name) {
+                Some(current)
+                    if name == &"flag_uploaded" || current.eq_ignore_ascii_case(dtype) => {}
+                Some(current) => {
+                    log::warn!(
+                        "Column type changed: {}.{name} {current} -> {dtype}",
+                        dconfig.name
+                    );
+                    return Err(SQLiteErrorType::NotMatch);
+                }
+                None => statements.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {name} {dtype};",
+                    dconfig.name
+                )),
             }
         }
 
-        for cfg_name in &dconfig.formation {
-            if !set.contains(&cfg_name.sqlite3.name) {
+        for name in columns.keys() {
+            // health columns are kept if `sitest` is removed
+            if !MANAGED_COLUMNS.contains(&name.as_str())
+                && !HEALTH_COLUMNS.contains(&name.as_str())
+                && !expected.iter().any(|(val, _)| val == name)
+            {
+                log::warn!("Column removed: {}.{name}", dconfig.name);
                 return Err(SQLiteErrorType::NotMatch);
             }
         }
     }
-    Ok(())
+
+    if create_headers_table(&connection).is_err() || create_schema_table(&connection).is_err() {
+        return Err(SQLiteErrorType::Invalid);
+    }
+    // columns of the headers table are only added
+    let columns = get_table_info(&connection, "headers")?;
+    for (name, dtype) in HEADERS_COLUMNS.iter() {
+        if !columns.contains_key(*name) {
+            statements.push(format!("ALTER TABLE headers ADD COLUMN {name} {dtype};"));
+        }
+    }
+    if statements.is_empty() {
+        return Ok(false);
+    }
+
+    let version = get_schema_version(&connection) + 1;
+    let ret = connection
+        .execute("BEGIN;")
+        .and_then(|_| {
+            statements
+                .iter()
+                .try_for_each(|val| connection.execute(val))
+        })
+        .and_then(|_| set_schema_version(&connection, version))
+        .and_then(|_| connection.execute("COMMIT;"));
+    if let Err(e) = ret {
+        log::error!("Migrate database failed: {e} - {:?}", path.as_ref());
+        let _ = connection.execute("ROLLBACK;");
+        return Err(SQLiteErrorType::Unknown);
+    }
+
+    let path = path.as_ref();
+    log::info!(target: "info", "Migrate database to version {version}: {path:?} {statements:?}");
+    Ok(true)
 }
 
-fn parse_rawdata(
+// json value of a column, binary is not supported
+pub fn to_json_value(value: sqlite::Value) -> serde_json::Value {
+    match value {
+        sqlite::Value::Integer(val) => serde_json::json!(val),
+        sqlite::Value::Float(val) => serde_json::json!(val),
+        sqlite::Value::String(val) => serde_json::json!(val),
+        sqlite::Value::Binary(_) | sqlite::Value::Null => serde_json::Value::Null,
+    }
+}
+
+// typed value of a rawdata word, numbers are bound as INTEGER or REAL
+fn parse_value(subdata: &str) -> sqlite::Value {
+    if let Ok(val) = subdata.parse::<i64>() {
+        sqlite::Value::Integer(val)
+    } else if let Ok(val) = subdata.parse::<f64>() {
+        sqlite::Value::Float(val)
+    } else {
+        sqlite::Value::String(subdata.to_string())
+    }
+}
+
+pub fn parse_rawdata(
     rawdata: &str,
     config: &CodecConfigMetadata,
-) -> (Option<String>, Option<NaiveDateTime>) {
+) -> (Option<Vec<sqlite::Value>>, Option<NaiveDateTime>) {
     let mut result = (None, None);
+    let Some((_, rawdata)) = config.expand_layout(rawdata) else {
+        log::error!("Layout not found: {rawdata:?}");
+        return result;
+    };
     let mut buf = Vec::new();
     let mut words = rawdata.split(',');
 
@@ -138,6 +331,11 @@ fn parse_rawdata(
             return result;
         };
 
+        if dtype.rust.major_datetime != Some(true) && dtype.spec.is_missing(subdata) {
+            buf.push(sqlite::Value::Null);
+            continue;
+        }
+
         match (dtype.rust.dtype.as_str(), dtype.rust.unit.as_deref()) {
             ("text", Some("%Y%m%d%H%M")) => {
                 let Some(formation) = &dtype.sqlite3.unit else {
@@ -149,7 +347,9 @@ fn parse_rawdata(
                     log::error!("Invalid data!");
                     return result;
                 };
-                buf.push(temp.format("'%Y-%m-%d %H:%M:%S'").to_string());
+                buf.push(sqlite::Value::String(
+                    temp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ));
                 if dtype.rust.major_datetime == Some(true) {
                     result.1.replace(temp.clone());
                 }
@@ -164,10 +364,10 @@ fn parse_rawdata(
                     log::error!("Invalid data! {subdata}");
                     return result;
                 };
-                buf.push(temp.format("'%H:%M:%S'").to_string());
+                buf.push(sqlite::Value::String(temp.format("%H:%M:%S").to_string()));
             }
-            ("text", _) => buf.push(format!("'{}'", subdata)),
-            _ => buf.push(subdata.to_string()),
+            ("text", _) => buf.push(sqlite::Value::String(subdata.to_string())),
+            _ => buf.push(parse_value(subdata)),
         }
     }
 
@@ -175,10 +375,67 @@ fn parse_rawdata(
     if config.stx_etx == Some(true) && words.next() != Some("\u{3}") {
         return result;
     }
-    result.0.replace(buf.join(","));
+    result.0.replace(buf);
     result
 }
 
+fn from_json_value(value: &serde_json::Value) -> sqlite::Value {
+    match value {
+        serde_json::Value::Number(val) => match (val.as_i64(), val.as_f64()) {
+            (Some(val), _) => sqlite::Value::Integer(val),
+            (None, Some(val)) => sqlite::Value::Float(val),
+            _ => sqlite::Value::Null,
+        },
+        serde_json::Value::String(val) => sqlite::Value::String(val.clone()),
+        serde_json::Value::Bool(val) => sqlite::Value::Integer(*val as i64),
+        _ => sqlite::Value::Null,
+    }
+}
+
+// formation is matched by `rust.name`, None if it isn't a field of the record
+pub fn parse_record(
+    record: &CwbRecord,
+    config: &CodecConfigMetadata,
+) -> (Option<Vec<sqlite::Value>>, Option<NaiveDateTime>) {
+    let fields = record.to_json();
+    let mut buf = Vec::new();
+    for dtype in config.formation.iter() {
+        let Some(value) = fields.get(&dtype.rust.name) else {
+            log::debug!("Field not found in record: {}", dtype.rust.name);
+            return (None, None);
+        };
+        buf.push(from_json_value(value));
+    }
+    (Some(buf), Some(record.dtime()))
+}
+
+// decode the status code of a parsed row, None if `sitest` is not configured or missing
+fn get_sensor_health(row: &[sqlite::Value], config: &CodecConfigMetadata) -> Option<SensorHealth> {
+    let field = config.sitest.as_ref()?.get_field();
+    let idx = config
+        .formation
+        .iter()
+        .position(|val| val.rust.name == field)?;
+    match row.get(idx)? {
+        sqlite::Value::String(code) => config.decode_sitest(code),
+        _ => None,
+    }
+}
+
+// use the typed record if any, rawdata is parsed by `formation` otherwise
+pub fn parse_payload(
+    msg: &MsgPayload,
+    idx: usize,
+    config: &CodecConfigMetadata,
+) -> (Option<Vec<sqlite::Value>>, Option<NaiveDateTime>) {
+    if let Some(record) = msg.get_record(idx) {
+        if let ret @ (Some(_), _) = parse_record(record, config) {
+            return ret;
+        }
+    }
+    parse_rawdata(&msg.value[idx], config)
+}
+
 fn check_sqlfile(config: &SystemConfig) {
     for (key, val) in config.codec.iter() {
         let Some(cfg_sqlite3) = val.sqlite3.as_ref() else {
@@ -202,14 +459,14 @@ fn check_sqlfile(config: &SystemConfig) {
         };
 
         let filepath = Path::new(&cfg_sqlite3.directory).join(lastfile);
-        match check_column(&filepath, &val) {
+        match migrate_db(&filepath, val) {
             Err(SQLiteErrorType::NotMatch) => {
-                log::info!("check_column: {:?}", SQLiteErrorType::NotMatch);
+                log::info!("migrate_db: {:?}", SQLiteErrorType::NotMatch);
                 if let Err(e) = backup_file(&filepath) {
                     log::error!("System Error. {e}");
                 }
             }
-            Ok(()) => {}
+            Ok(_) => {}
             ret => {
                 log::error!("{:?}", ret);
             }
@@ -217,117 +474,554 @@ fn check_sqlfile(config: &SystemConfig) {
     }
 }
 
-pub fn setup_sqlite3_recorder(
-    receiver: Receiver<Arc<MsgPayload>>,
+// databases are seperated by date, only the recent ones are kept open
+const MAX_CONNECTIONS: usize = 8;
+
+fn open_db(
+    filepath: &Path,
+    config: &CodecConfigBase,
+) -> Result<sqlite::Connection, Box<dyn Error + 'static>> {
+    if let Some(root) = filepath.parent() {
+        create_dir_all(root)?;
+    }
+    if filepath.exists() {
+        if let Err(SQLiteErrorType::NotMatch) = migrate_db(filepath, config) {
+            backup_file(filepath)?;
+        }
+    }
+    if !filepath.exists() {
+        create_db(filepath, config)?;
+    }
+    Ok(sqlite::open(filepath)?)
+}
+
+// insert rows with a prepared statement in a single transaction,
+// a failed row rolls back the whole payload
+fn insert_rows(
+    connection: &sqlite::Connection,
+    statement: &str,
+    rows: &[Vec<sqlite::Value>],
+) -> Result<usize, Box<dyn Error + 'static>> {
+    connection.execute("BEGIN;")?;
+    let ret = connection
+        .prepare(statement)
+        .map_err(|e| e.to_string())
+        .and_then(|mut statement| {
+            for row in rows {
+                statement
+                    .bind(&row[..])
+                    .and_then(|_| statement.next())
+                    .and_then(|_| statement.reset())
+                    .map_err(|e| format!("{e} - {row:?}"))?;
+            }
+            Ok(rows.len())
+        });
+
+    match ret {
+        Ok(num) => {
+            connection.execute("COMMIT;")?;
+            Ok(num)
+        }
+        Err(e) => {
+            if let Err(e) = connection.execute("ROLLBACK;") {
+                log::error!("Rollback failed: {e}");
+            }
+            Err(e.into())
+        }
+    }
+}
+
+fn get_connection<'a>(
+    connections: &'a mut HashMap<PathBuf, sqlite::Connection>,
+    filepath: &Path,
+    config: &CodecConfigBase,
+) -> Result<&'a sqlite::Connection, Box<dyn Error + 'static>> {
+    if !connections.contains_key(filepath) {
+        if connections.len() >= MAX_CONNECTIONS {
+            connections.clear();
+        }
+        let connection = open_db(filepath, config)?;
+        connections.insert(filepath.to_path_buf(), connection);
+    }
+    Ok(&connections[filepath])
+}
+
+// id of the header version in `headers`, insert it if the database hasn't got it yet
+fn get_header_id(
+    connection: &sqlite::Connection,
+    tablename: &str,
+    header: &[String],
+) -> Result<i64, Box<dyn Error + 'static>> {
+    let text = header.concat();
+    let query =
+        "SELECT id FROM headers WHERE tablename = ? AND header = ? ORDER BY id DESC LIMIT 1;";
+    let mut statement = connection.prepare(query)?;
+    statement.bind(&[tablename, text.as_str()][..])?;
+    if let sqlite::State::Row = statement.next()? {
+        return Ok(statement.read::<i64, _>("id")?);
+    }
+
+    let toa5 = Toa5Header::parse(header).unwrap_or_default();
+    let created_at = Local::now().format(DTAETIME_FMT).to_string();
+    let values = [
+        tablename.to_string(),
+        text,
+        created_at,
+        toa5.station,
+        toa5.logger_model,
+        toa5.logger_serial,
+        toa5.os_version,
+        toa5.program,
+        toa5.program_signature,
+        toa5.table,
+        toa5.fields.join(","),
+        toa5.units.join(","),
+        toa5.processing.join(","),
+    ];
+    let query = format!(
+        "INSERT INTO headers ({}) VALUES ({});",
+        HEADERS_COLUMNS.map(|(name, _)| name).join(","),
+        vec!["?"; HEADERS_COLUMNS.len()].join(",")
+    );
+    let mut statement = connection.prepare(query)?;
+    let values = values.iter().map(|val| val.as_str()).collect::<Vec<_>>();
+    statement.bind(&values[..])?;
+    statement.next()?;
+
+    let mut statement = connection.prepare("SELECT last_insert_rowid() AS id;")?;
+    statement.next()?;
+    Ok(statement.read::<i64, _>("id")?)
+}
+
+pub struct Sqlite3Recorder {
     config: Arc<SystemConfig>,
-) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
-    for (_key, val) in config.codec.iter() {
-        if let Some(cfg) = val.sqlite3.as_ref() {
-            create_dir_all(&cfg.directory)?;
+    header_table: HeaderTable,
+    connections: HashMap<PathBuf, sqlite::Connection>,
+    // (database, header hash) -> headers.id
+    header_ids: HashMap<(PathBuf, u64), i64>,
+}
+
+impl Sqlite3Recorder {
+    pub fn new(config: Arc<SystemConfig>) -> Self {
+        Sqlite3Recorder {
+            config,
+            header_table: HeaderTable::new(),
+            connections: HashMap::new(),
+            header_ids: HashMap::new(),
         }
     }
 
-    let handler = thread::spawn(move || {
-        let mut header_table = HeaderTable::new();
-        check_sqlfile(&config);
+    // the active header of `msg` in the database, None for data without header
+    fn get_header_id(&mut self, msg: &MsgPayload, filepath: &Path, tablename: &str) -> Option<i64> {
+        let header = self.header_table.get(&gen_headertable_key(msg))?;
+        let key = (filepath.to_path_buf(), header.hash);
+        if let Some(id) = self.header_ids.get(&key) {
+            return Some(*id);
+        }
 
-        loop {
-            while let Ok(msg) = receiver.recv() {
-                // for header msg
-                if msg.update_header {
-                    let key = gen_headertable_key(&msg);
-                    if is_update_header(&mut header_table, &key, &msg.value) {
-                        check_sqlfile(&config);
-                    }
-                    continue;
-                }
+        let connection = self.connections.get(filepath)?;
+        match get_header_id(connection, tablename, &header.header) {
+            Ok(id) => {
+                self.header_ids.insert(key, id);
+                Some(id)
+            }
+            Err(e) => {
+                log::error!("Insert header failed: {e} - {filepath:?}");
+                None
+            }
+        }
+    }
+}
 
-                // for data msg
-                let Some(cfg) = config.codec.get(&msg.tag) else {
-                    log::error!("Unsupport tag: {:?}", msg.tag);
-                    continue;
-                };
+impl Sink for Sqlite3Recorder {
+    fn setup(&mut self) -> Result<(), Box<dyn Error + 'static>> {
+        for (_key, val) in self.config.codec.iter() {
+            if let Some(cfg) = val.sqlite3.as_ref() {
+                create_dir_all(&cfg.directory)?;
+            }
+        }
+        check_sqlfile(&self.config);
+        Ok(())
+    }
 
-                let Some(cfg_sqlite3) = cfg.sqlite3.as_ref() else {
-                    log::info!("Unsupport record rawdata: {:?}", msg.tag);
-                    continue;
-                };
+    fn handle(&mut self, msg: Arc<MsgPayload>) {
+        // for header msg
+        if msg.update_header {
+            let key = gen_headertable_key(&msg);
+            if is_update_header(&mut self.header_table, &key, &msg.value) {
+                // database may be moved to backup
+                self.connections.clear();
+                self.header_ids.clear();
+                check_sqlfile(&self.config);
+            }
+            return;
+        }
 
-                let Some(dconfig) = cfg.get_data_config(&msg.dkind) else {
-                    log::error!("Invalid: {msg:?}");
-                    continue;
-                };
+        // for data msg
+        let config = self.config.clone();
+        let Some(cfg) = config.codec.get(&msg.tag) else {
+            log::error!("Unsupport tag: {:?}", msg.tag);
+            return;
+        };
 
-                let columnname = dconfig
-                    .formation
-                    .iter()
-                    .map(|mem| mem.sqlite3.name.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                for value in msg.value.iter() {
-                    let (Some(data_str), Some(time)) = parse_rawdata(value, dconfig) else {
-                        log::error!("Invalid: {msg:?}");
-                        continue;
-                    };
-
-                    let mut opts = HashMap::new();
-                    opts.insert(
-                        "datetime".to_string(),
-                        time.format(DTAETIME_FMT).to_string(),
-                    );
-                    let filepath =
-                        generate_db_filepath(&cfg.tag, cfg_sqlite3, dconfig, &opts).unwrap();
-
-                    if let Some(root) = filepath.parent() {
-                        if let Err(e) = create_dir_all(&root) {
-                            log::error!("System Error. {e}");
-                        }
-                    }
-
-                    if !filepath.exists() {
-                        match create_db(&filepath, &cfg) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                log::error!("Create database failed: {e} - {filepath:?} - {msg:?}");
-                                continue;
-                            }
-                        }
-                    }
-
-                    if let Ok(connection) = sqlite::open(filepath) {
-                        let statement = if dconfig.raw_save == Some(true) {
-                            format!(
-                                "INSERT into {} ({},rawdata) values ({},'{}');",
-                                &dconfig.name, columnname, data_str, value
-                            )
-                        } else {
-                            format!(
-                                "INSERT into {} ({}) values ({});",
-                                &dconfig.name, columnname, data_str
-                            )
-                        };
-                        match connection.execute(&statement) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                log::error!("Insert data failed: {e} - {statement}");
-                                continue;
-                            }
-                        }
-                    } else {
-                        log::error!("Open database failed: {msg:?}");
-                        continue;
-                    }
+        let Some(cfg_sqlite3) = cfg.sqlite3.as_ref() else {
+            log::info!("Unsupport record rawdata: {:?}", msg.tag);
+            return;
+        };
+
+        let Some(dconfig) = cfg.get_data_config(&msg.dkind) else {
+            log::error!("Invalid: {msg:?}");
+            return;
+        };
+
+        let mut columnname = dconfig
+            .formation
+            .iter()
+            .map(|mem| mem.sqlite3.name.to_string())
+            .collect::<Vec<_>>();
+        if dconfig.raw_save == Some(true) {
+            columnname.push("rawdata".to_string());
+        }
+        if dconfig.sitest.is_some() {
+            columnname.extend(HEALTH_COLUMNS.iter().map(|name| name.to_string()));
+        }
+        columnname.push("missing_count".to_string());
+        columnname.push("header_id".to_string());
+        let statement = format!(
+            "INSERT into {} ({}) values ({});",
+            &dconfig.name,
+            columnname.join(","),
+            vec!["?"; columnname.len()].join(",")
+        );
+
+        // rows of each database file, in order of appearance
+        let mut batches: Vec<(PathBuf, Vec<Vec<sqlite::Value>>)> = Vec::new();
+        for (idx, value) in msg.value.iter().enumerate() {
+            let (Some(mut row), Some(time)) = parse_payload(&msg, idx, dconfig) else {
+                log::error!("Invalid: {msg:?}");
+                continue;
+            };
+            let missing = row
+                .iter()
+                .filter(|val| matches!(val, sqlite::Value::Null))
+                .count();
+            let health = get_sensor_health(&row, dconfig);
+            if dconfig.raw_save == Some(true) {
+                row.push(sqlite::Value::String(value.to_string()));
+            }
+            if dconfig.sitest.is_some() {
+                match health {
+                    Some(health) => row.extend(health.to_sqlite3_values()),
+                    None => row.extend(HEALTH_COLUMNS.map(|_| sqlite::Value::Null)),
                 }
             }
+            row.push(sqlite::Value::Integer(missing as i64));
+
+            let mut opts = HashMap::new();
+            opts.insert(
+                "datetime".to_string(),
+                time.format(DTAETIME_FMT).to_string(),
+            );
+            let Some(filepath) = generate_db_filepath(&cfg.tag, cfg_sqlite3, dconfig, &opts) else {
+                log::error!("Generate database path failed: {msg:?}");
+                continue;
+            };
+
+            match batches.iter_mut().find(|(path, _)| path == &filepath) {
+                Some((_, rows)) => rows.push(row),
+                None => batches.push((filepath, vec![row])),
+            }
         }
-    });
-    Ok(handler)
+
+        for (filepath, mut rows) in batches {
+            if !self.connections.contains_key(&filepath) {
+                // database may be recreated when opened
+                self.header_ids.retain(|(path, _), _| path != &filepath);
+            }
+            if let Err(e) = get_connection(&mut self.connections, &filepath, cfg) {
+                log::error!("Open database failed: {e} - {filepath:?} - {msg:?}");
+                continue;
+            }
+
+            let header_id = match self.get_header_id(&msg, &filepath, &dconfig.name) {
+                Some(id) => sqlite::Value::Integer(id),
+                None => sqlite::Value::Null,
+            };
+            rows.iter_mut().for_each(|row| row.push(header_id.clone()));
+
+            let connection = &self.connections[&filepath];
+            if let Err(e) = insert_rows(connection, &statement, &rows) {
+                log::error!("Insert data failed: {e} - {filepath:?} - {statement}");
+                // reopen on next message
+                self.connections.remove(&filepath);
+                self.header_ids.retain(|(path, _), _| path != &filepath);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use sqlite::State;
+    use sqlite::{State, Value};
+
+    use super::*;
+
+    fn formation(name: &str, dtype: &str, unit: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "spec": {"name": name, "description": "", "dtype": dtype},
+            "rust": {"name": name, "dtype": dtype, "unit": unit, "major_datetime": unit.is_some()},
+            "sqlite3": {"name": name, "dtype": "TEXT", "unit": unit}
+        })
+    }
+
+    #[test]
+    fn parse_typed_values() {
+        let config: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["MN"],
+            "formation": [
+                formation("datetime", "text", Some("%Y%m%d%H%M")),
+                formation("station", "text", None),
+                formation("temp", "float", None),
+                formation("count", "integer", None)
+            ]
+        }))
+        .unwrap();
+
+        let (row, time) = parse_rawdata("202501091555,it's,12.5,3", &config);
+        assert_eq!(
+            row.unwrap(),
+            vec![
+                Value::String("2025-01-09 15:55:00".to_string()),
+                Value::String("it's".to_string()),
+                Value::Float(12.5),
+                Value::Integer(3),
+            ]
+        );
+        assert!(time.is_some());
+    }
+
+    #[test]
+    fn parse_missing_values() {
+        let mut temp = formation("temp", "float", None);
+        temp["spec"]["missing"] = serde_json::json!(["-9999", "NAN"]);
+        let config: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["MN"],
+            "formation": [
+                formation("datetime", "text", Some("%Y%m%d%H%M")),
+                temp.clone(),
+                temp,
+                formation("count", "integer", None)
+            ]
+        }))
+        .unwrap();
+
+        let (row, _) = parse_rawdata("202501091555,-9999.0,NAN,/////", &config);
+        assert_eq!(row.unwrap()[1..], [Value::Null, Value::Null, Value::Null]);
+        let (row, _) = parse_rawdata("202501091555,12.5,,3", &config);
+        assert_eq!(
+            row.unwrap()[1..],
+            [Value::Float(12.5), Value::Null, Value::Integer(3)]
+        );
+
+        // major datetime is never missing
+        assert!(parse_rawdata(",12.5,1,3", &config).0.is_none());
+    }
+
+    #[test]
+    fn parse_layouts() {
+        let mut names = vec!["stationid".to_string(), "dkind".to_string()];
+        names.extend((1..=12).map(|val| format!("dd_vmc{:03}", val * 10)));
+        names.push("dd_sitest".to_string());
+        let mut fields = names
+            .iter()
+            .map(|name| formation(name, "text", None))
+            .collect::<Vec<_>>();
+        fields.insert(2, formation("dtime", "text", Some("%Y%m%d%H%M")));
+        let mut config: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["SM"],
+            "stx_etx": true,
+            "formation": fields,
+            "layouts": [{"name": "short", "omit": ["dd_vmc110"]}]
+        }))
+        .unwrap();
+
+        let full = "\u{2},466920,SM,202501091555,1,2,3,4,5,6,7,8,9,10,11,12,0000,\u{3}";
+        let short = "\u{2},466920,SM,202501091555,1,2,3,4,5,6,7,8,9,10,12,0000,\u{3}";
+        assert_eq!(config.expand_layout(full).unwrap().0, "default");
+        let (layout, rawdata) = config.expand_layout(short).unwrap();
+        assert_eq!(layout, "short");
+        assert_eq!(rawdata, full.replace(",11,", ",,"));
+        assert!(config.expand_layout("\u{2},466920,SM,\u{3}").is_none());
+
+        let row = parse_rawdata(short, &config).0.unwrap();
+        assert_eq!(row[13], Value::Null);
+        assert_eq!(row[14], Value::String("12".to_string()));
+
+        // typed record is parsed by the matched layout
+        let mut msg = MsgPayload::new("CWB", "SM", vec![short.to_string()]);
+        msg.parse_records();
+        assert!(msg.get_record(0).is_none());
+        assert_eq!(msg.apply_layouts(&config), vec![Some("short")]);
+        let record = msg.get_record(0).unwrap();
+        assert_eq!(record.to_json()["dd_vmc110"], serde_json::Value::Null);
+
+        // layout selected by config
+        config.layout = Some("short".to_string());
+        assert!(config.expand_layout(full).is_none());
+    }
+
+    #[test]
+    fn parse_typed_record() {
+        let config: CodecConfigMetadata = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "dkind": ["SM"],
+            "formation": [
+                formation("stationid", "text", None),
+                formation("dtime", "text", Some("%Y%m%d%H%M")),
+                formation("dd_vmc010", "float", None)
+            ]
+        }))
+        .unwrap();
+
+        let mut msg = MsgPayload::new(
+            "CWB",
+            "SM",
+            vec!["\u{2},466920,SM,202501091555,1,2,3,4,5,6,7,8,9,10,11,12,0000,\u{3}".to_string()],
+        );
+        msg.parse_records();
+        let record = msg.get_record(0).unwrap();
+        let (row, time) = parse_record(record, &config);
+        assert_eq!(
+            row.unwrap(),
+            vec![
+                Value::String("466920".to_string()),
+                Value::String("2025-01-09 15:55:00".to_string()),
+                Value::Float(1.0),
+            ]
+        );
+        assert!(time.is_some());
+
+        // unknown field falls back to rawdata
+        let mut config = config;
+        config.formation[0].rust.name = "station".to_string();
+        assert!(parse_record(record, &config).0.is_none());
+        assert!(parse_payload(&msg, 0, &config).0.is_none());
+    }
+
+    fn codec(columns: &[(&str, &str)]) -> CodecConfigBase {
+        let formation = columns
+            .iter()
+            .map(|(name, dtype)| {
+                let mut val = formation(name, "float", None);
+                val["sqlite3"]["dtype"] = serde_json::json!(dtype);
+                val
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "tag": "TEST",
+            "metadatas": [{"name": "data", "dkind": ["MN"], "formation": formation}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn migrate_columns() {
+        let path = std::env::temp_dir().join("naho_migrate.sql");
+        let path = path.as_path();
+        let _ = std::fs::remove_file(path);
+
+        let config = codec(&[("temp", "REAL")]);
+        create_db(path, &config).unwrap();
+        assert!(!migrate_db(path, &config).unwrap());
+
+        // new column is added
+        let config = codec(&[("temp", "REAL"), ("rh", "REAL")]);
+        assert!(migrate_db(path, &config).unwrap());
+        let connection = sqlite::open(path).unwrap();
+        assert!(get_table_info(&connection, "data")
+            .unwrap()
+            .contains_key("rh"));
+        assert_eq!(get_schema_version(&connection), 2);
+
+        // type change and removed column are incompatible
+        let config = codec(&[("temp", "TEXT"), ("rh", "REAL")]);
+        assert!(matches!(
+            migrate_db(path, &config),
+            Err(SQLiteErrorType::NotMatch)
+        ));
+        let config = codec(&[("temp", "REAL")]);
+        assert!(matches!(
+            migrate_db(path, &config),
+            Err(SQLiteErrorType::NotMatch)
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn header_versions() {
+        let path = std::env::temp_dir().join("naho_headers.sql");
+        let path = path.as_path();
+        let _ = std::fs::remove_file(path);
+        create_db(path, &codec(&[("temp", "REAL")])).unwrap();
+        let connection = sqlite::open(path).unwrap();
+
+        let header = [
+            "\"TOA5\",\"NAHO\",\"CR1000X\",\"1\",\"OS\",\"naho.CR1X\",\"1\",\"Table1\"\r\n",
+            "\"TIMESTAMP\",\"temp\"\r\n",
+            "\"TS\",\"Deg C\"\r\n",
+            "\"\",\"Avg\"\r\n",
+        ]
+        .map(|val| val.to_string());
+        let id = get_header_id(&connection, "data", &header).unwrap();
+        assert_eq!(get_header_id(&connection, "data", &header).unwrap(), id);
+
+        let mut statement = connection
+            .prepare("SELECT station, fields FROM headers WHERE id = ?;")
+            .unwrap();
+        statement.bind((1, id)).unwrap();
+        assert_eq!(statement.next().unwrap(), State::Row);
+        assert_eq!(statement.read::<String, _>("station").unwrap(), "NAHO");
+        assert_eq!(
+            statement.read::<String, _>("fields").unwrap(),
+            "TIMESTAMP,temp"
+        );
+
+        // new program is a new version
+        let mut header = header.to_vec();
+        header[0] = header[0].replace("naho.CR1X", "naho_v2.CR1X");
+        assert_ne!(get_header_id(&connection, "data", &header).unwrap(), id);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn insert_rollback() {
+        let connection = sqlite::open(":memory:").unwrap();
+        connection
+            .execute("CREATE TABLE data (id INTEGER PRIMARY KEY, temp REAL NOT NULL);")
+            .unwrap();
+        let count = || {
+            let mut statement = connection
+                .prepare("SELECT COUNT(*) AS num FROM data;")
+                .unwrap();
+            statement.next().unwrap();
+            statement.read::<i64, _>("num").unwrap()
+        };
+
+        // second row violates NOT NULL, the first one isn't kept either
+        let statement = "INSERT INTO data (id, temp) VALUES (?, ?);";
+        let rows = vec![
+            vec![Value::Integer(1), Value::Float(1.5)],
+            vec![Value::Integer(2), Value::Null],
+        ];
+        assert!(insert_rows(&connection, statement, &rows).is_err());
+        assert_eq!(count(), 0);
+
+        assert_eq!(insert_rows(&connection, statement, &rows[..1]).unwrap(), 1);
+        assert_eq!(count(), 1);
+    }
 
     #[test]
     fn setup_data() {
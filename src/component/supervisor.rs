@@ -0,0 +1,293 @@
+use std::{
+    any::Any,
+    error::Error,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use super::utils::{backoff::Backoff, shutdown::Shutdown};
+
+const RESTART_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(60);
+// backoff starts over if the thread has run longer than this
+const STABLE_PERIOD: Duration = Duration::from_secs(300);
+
+pub type SpawnFn = Box<dyn Fn() -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> + Send>;
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from("unknown panic payload")
+    }
+}
+
+// restart state of a component thread
+pub struct Supervised {
+    name: String,
+    handler: Option<JoinHandle<usize>>,
+    started_at: Instant,
+    retry_at: Option<Instant>,
+    backoff: Backoff,
+    restarts: usize,
+    handled: usize,
+}
+
+impl Supervised {
+    pub fn new(name: &str, handler: JoinHandle<usize>) -> Self {
+        Supervised {
+            name: name.to_string(),
+            handler: Some(handler),
+            started_at: Instant::now(),
+            retry_at: None,
+            backoff: Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY),
+            restarts: 0,
+            handled: 0,
+        }
+    }
+
+    // the first start failed, it's retried with backoff
+    pub fn failed(name: &str) -> Self {
+        let mut backoff = Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY);
+        let delay = backoff.next_delay();
+        log::info!(target: "info", "Restart {name} in {delay:?}");
+        Supervised {
+            name: name.to_string(),
+            handler: None,
+            started_at: Instant::now(),
+            retry_at: Some(Instant::now() + delay),
+            backoff,
+            restarts: 0,
+            handled: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handler.as_ref().is_none_or(|val| val.is_finished())
+    }
+
+    // collect the stopped thread, return true if it should be started again now
+    pub fn need_restart(&mut self) -> bool {
+        if self.handler.as_ref().is_some_and(|val| val.is_finished()) {
+            let handler = self.handler.take().unwrap();
+            match handler.join() {
+                Ok(num) => {
+                    self.handled += num;
+                    log::error!("{} stopped unexpectedly.", self.name);
+                }
+                Err(e) => log::error!("{} panicked: {}", self.name, panic_message(&*e)),
+            }
+
+            if self.started_at.elapsed() >= STABLE_PERIOD {
+                self.backoff = Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY);
+            }
+            let delay = self.backoff.next_delay();
+            log::info!(target: "info", "Restart {} in {delay:?}", self.name);
+            self.retry_at = Some(Instant::now() + delay);
+        }
+
+        self.retry_at.is_some_and(|val| Instant::now() >= val)
+    }
+
+    pub fn restarted(&mut self, ret: Result<JoinHandle<usize>, Box<dyn Error + 'static>>) {
+        match ret {
+            Ok(handler) => {
+                self.restarts += 1;
+                self.handler = Some(handler);
+                self.started_at = Instant::now();
+                self.retry_at = None;
+                let (name, restarts) = (&self.name, self.restarts);
+                log::info!("Restart {name} success, restarts: {restarts}");
+                log::info!(target: "info", "Restart {name} success, restarts: {restarts}");
+            }
+            Err(e) => {
+                let delay = self.backoff.next_delay();
+                log::error!("Restart {} failed: {e}, retry in {delay:?}", self.name);
+                self.retry_at = Some(Instant::now() + delay);
+            }
+        }
+    }
+
+    // return the number of handled messages of all runs
+    pub fn join(mut self) -> Result<usize, String> {
+        let Some(handler) = self.handler.take() else {
+            return Ok(self.handled);
+        };
+        match handler.join() {
+            Ok(num) => Ok(self.handled + num),
+            Err(e) => Err(panic_message(&*e)),
+        }
+    }
+}
+
+// restart reader threads which stopped before shutdown
+pub struct Supervisor {
+    shutdown: Shutdown,
+    components: Vec<(SpawnFn, Supervised)>,
+}
+
+impl Supervisor {
+    pub fn new(shutdown: Shutdown) -> Self {
+        Supervisor {
+            shutdown,
+            components: Vec::new(),
+        }
+    }
+
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    // a failed start is returned and retried by `supervise`
+    pub fn spawn(&mut self, name: &str, spawn: SpawnFn) -> Result<(), Box<dyn Error + 'static>> {
+        match spawn() {
+            Ok(handler) => {
+                self.components
+                    .push((spawn, Supervised::new(name, handler)));
+                Ok(())
+            }
+            Err(e) => {
+                self.components.push((spawn, Supervised::failed(name)));
+                Err(e)
+            }
+        }
+    }
+
+    pub fn supervise(&mut self) {
+        if self.shutdown.is_triggered() {
+            return;
+        }
+        for (spawn, component) in self.components.iter_mut() {
+            if component.need_restart() {
+                component.restarted(spawn());
+            }
+        }
+    }
+
+    // all threads are stopped and won't be restarted
+    pub fn is_finished(&self) -> bool {
+        (self.components.is_empty() || self.shutdown.is_triggered())
+            && self.components.iter().all(|(_, val)| val.is_finished())
+    }
+
+    // (name, restarts)
+    pub fn restart_counts(&self) -> Vec<(String, usize)> {
+        self.components
+            .iter()
+            .map(|(_, val)| (val.name().to_string(), val.restarts()))
+            .collect()
+    }
+
+    // return false if any thread panicked
+    pub fn join(self) -> bool {
+        let mut result = true;
+        for (_, component) in self.components {
+            let name = component.name().to_string();
+            let restarts = component.restarts();
+            match component.join() {
+                Ok(num) => log::info!(
+                    target: "info",
+                    "Reader {name} sent {num} messages, restarts: {restarts}"
+                ),
+                Err(e) => {
+                    log::error!("Reader {name} panicked: {e}");
+                    result = false;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn restart_after_panic() {
+        let shutdown = Shutdown::new();
+        let mut supervisor = Supervisor::new(shutdown.clone());
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let flag = shutdown.clone();
+        let spawn: SpawnFn = Box::new(move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            let flag = flag.clone();
+            Ok(thread::spawn(move || {
+                if run == 0 {
+                    panic!("first run");
+                }
+                while flag.sleep(Duration::from_millis(10)) {}
+                1
+            }))
+        });
+        supervisor.spawn("test", spawn).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while runs.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+            supervisor.supervise();
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(supervisor.restart_counts(), vec![("test".to_string(), 1)]);
+        assert!(!supervisor.is_finished());
+
+        shutdown.trigger();
+        while !supervisor.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(supervisor.join());
+    }
+
+    #[test]
+    fn retry_failed_start() {
+        let shutdown = Shutdown::new();
+        let mut supervisor = Supervisor::new(shutdown.clone());
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let spawn: SpawnFn = Box::new(move || {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(String::from("device not found").into());
+            }
+            Ok(thread::spawn(|| 1))
+        });
+        assert!(supervisor.spawn("test", spawn).is_err());
+        assert!(!supervisor.is_finished());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while runs.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+            supervisor.supervise();
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(supervisor.restart_counts(), vec![("test".to_string(), 1)]);
+        shutdown.trigger();
+        assert!(supervisor.join());
+    }
+
+    #[test]
+    fn payload_message() {
+        let ret = thread::spawn(|| panic!("boom {}", 1)).join().unwrap_err();
+        assert_eq!(panic_message(&*ret), "boom 1");
+        let ret = thread::spawn(|| panic!("boom")).join().unwrap_err();
+        assert_eq!(panic_message(&*ret), "boom");
+    }
+}
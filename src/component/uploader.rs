@@ -0,0 +1,377 @@
+use std::{
+    error::Error,
+    path::Path,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::config::{SystemConfig, UploaderConfig};
+
+use super::{
+    qc_level1::sqlite_get_columns,
+    receiver_sqlite::to_json_value,
+    utils::{backoff::Backoff, files::collect_files, shutdown::Shutdown},
+};
+
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(600);
+// the sqlite3 recorder may be writing the same database
+const BUSY_TIMEOUT: u64 = 5000; // ms
+
+// `flag_uploaded` of rows rejected by the server, they are not sent again
+const FLAG_REJECTED: i64 = -1;
+
+// only a bad payload is rejected for good, auth and routing errors (401, 403, 404)
+// are retried, they are fixed by the config or the server
+fn is_rejected(status: u16) -> bool {
+    matches!(status, 400 | 422)
+}
+
+// post rows with `flag_uploaded = FALSE`, rows are marked only after a 2xx response
+struct Uploader {
+    agent: ureq::Agent,
+    url: String,
+    batch_size: usize,
+}
+
+impl Uploader {
+    fn new(cfg: &UploaderConfig) -> Self {
+        Uploader {
+            agent: ureq::AgentBuilder::new().timeout(cfg.get_timeout()).build(),
+            url: cfg.url.clone(),
+            batch_size: cfg.get_batch_size(),
+        }
+    }
+
+    // Ok(Some(status)) if the batch is rejected permanently
+    fn post(&self, body: &Value) -> Result<Option<u16>, Box<dyn Error + 'static>> {
+        let ret = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string());
+        match ret {
+            Ok(resp) if (200..300).contains(&resp.status()) => Ok(None),
+            Ok(resp) => Err(format!("Unexpected status: {}", resp.status()).into()),
+            Err(ureq::Error::Status(code, _)) if is_rejected(code) => Ok(Some(code)),
+            Err(ureq::Error::Status(code, _)) => Err(format!("Rejected status: {code}").into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // (ids, rows) of the next batch
+    fn select_pending(
+        &self,
+        connection: &sqlite::Connection,
+        table: &str,
+    ) -> Result<(Vec<i64>, Vec<Value>), Box<dyn Error + 'static>> {
+        let query = format!(
+            "SELECT * FROM {table} WHERE IFNULL(flag_uploaded, FALSE) = FALSE \
+            ORDER BY id LIMIT {};",
+            self.batch_size
+        );
+        let mut statement = connection.prepare(query)?;
+        let columns = statement.column_names().to_vec();
+
+        let mut ids = Vec::new();
+        let mut rows = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            let mut row = Map::new();
+            for (idx, name) in columns.iter().enumerate() {
+                if name == "flag_uploaded" {
+                    continue;
+                }
+                row.insert(
+                    name.clone(),
+                    to_json_value(statement.read::<sqlite::Value, _>(idx)?),
+                );
+            }
+            ids.push(statement.read::<i64, _>("id")?);
+            rows.push(Value::Object(row));
+        }
+        Ok((ids, rows))
+    }
+
+    fn upload_table(
+        &self,
+        connection: &sqlite::Connection,
+        tag: &str,
+        table: &str,
+        database: &str,
+        shutdown: &Shutdown,
+    ) -> Result<usize, Box<dyn Error + 'static>> {
+        let mut counter = 0;
+        while !shutdown.is_triggered() {
+            let (ids, rows) = self.select_pending(connection, table)?;
+            if ids.is_empty() {
+                break;
+            }
+
+            let body = json!({
+                "tag": tag,
+                "table": table,
+                "database": database,
+                "rows": rows,
+            });
+            let rejected = self.post(&body)?;
+
+            let num = ids.len();
+            let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+            let flag = match rejected {
+                Some(status) => {
+                    log::error!(
+                        "Upload rejected: status {status}, {num} rows are skipped - {database} {}",
+                        table
+                    );
+                    FLAG_REJECTED.to_string()
+                }
+                None => {
+                    counter += num;
+                    String::from("TRUE")
+                }
+            };
+            connection.execute(format!(
+                "UPDATE {table} SET flag_uploaded = {flag} WHERE id IN ({});",
+                ids.join(",")
+            ))?;
+
+            if num < self.batch_size {
+                break;
+            }
+        }
+        Ok(counter)
+    }
+
+    fn upload_database(
+        &self,
+        path: &Path,
+        tag: &str,
+        tables: &[&str],
+        shutdown: &Shutdown,
+    ) -> Result<usize, Box<dyn Error + 'static>> {
+        let database = path
+            .file_name()
+            .and_then(|val| val.to_str())
+            .unwrap_or_default();
+        let connection = sqlite::open(path)?;
+        connection.execute(format!("PRAGMA busy_timeout = {BUSY_TIMEOUT};"))?;
+
+        let mut counter = 0;
+        for table in tables {
+            // table of another codec or created before `flag_uploaded`
+            let Ok(columns) = sqlite_get_columns(path, table) else {
+                continue;
+            };
+            if !columns.iter().any(|val| val == "flag_uploaded") {
+                continue;
+            }
+            counter += self.upload_table(&connection, tag, table, database, shutdown)?;
+        }
+        Ok(counter)
+    }
+
+    // (rows, failures), a failed database is logged and the next one is uploaded
+    fn upload_all(
+        &self,
+        config: &SystemConfig,
+        cfg: &UploaderConfig,
+        shutdown: &Shutdown,
+    ) -> (usize, usize) {
+        let mut counter = 0;
+        let mut failures = 0;
+        for (key, val) in config.codec.iter() {
+            if !cfg.is_enabled_tag(key) {
+                continue;
+            }
+            let Some(cfg_sqlite3) = val.sqlite3.as_ref() else {
+                continue;
+            };
+            if !Path::new(&cfg_sqlite3.directory).exists() {
+                continue;
+            }
+
+            let mut files = Vec::new();
+            let suffix = cfg_sqlite3.suffix.as_deref().unwrap_or("dat");
+            if let Err(e) = collect_files(&cfg_sqlite3.directory, suffix, &mut files) {
+                log::error!("Upload failed: {e} - {:?}", cfg_sqlite3.directory);
+                failures += 1;
+                continue;
+            }
+            files.sort();
+
+            let tables = val
+                .metadatas
+                .iter()
+                .map(|mem| mem.name.as_str())
+                .collect::<Vec<_>>();
+            for path in files {
+                if shutdown.is_triggered() {
+                    return (counter, failures);
+                }
+                match self.upload_database(&path, &val.tag, &tables, shutdown) {
+                    Ok(num) => counter += num,
+                    Err(e) => {
+                        log::error!("Upload failed: {e} - {path:?}");
+                        failures += 1;
+                    }
+                }
+            }
+        }
+        (counter, failures)
+    }
+}
+
+pub fn setup_uploader(
+    config: Arc<SystemConfig>,
+    shutdown: Shutdown,
+) -> Result<JoinHandle<usize>, Box<dyn Error + 'static>> {
+    let Some(cfg) = config.global.uploader.clone() else {
+        return Err(String::from("Uploader is not configured.").into());
+    };
+
+    let handler = thread::spawn(move || {
+        let uploader = Uploader::new(&cfg);
+        let mut backoff = Backoff::new(RETRY_INITIAL_DELAY, RETRY_MAX_DELAY);
+        let mut counter = 0;
+        loop {
+            let (num, failures) = uploader.upload_all(&config, &cfg, &shutdown);
+            if num > 0 {
+                log::info!(target: "info", "Uploaded {num} rows to {}", cfg.url);
+            }
+            counter += num;
+            let delay = if failures > 0 {
+                let delay = backoff.next_delay();
+                log::error!("Upload of {failures} databases failed, retry in {delay:?}");
+                delay
+            } else {
+                backoff = Backoff::new(RETRY_INITIAL_DELAY, RETRY_MAX_DELAY);
+                cfg.get_interval()
+            };
+            if !shutdown.sleep(delay) {
+                break;
+            }
+        }
+        log::info!("Uploader stopped.");
+        counter
+    });
+    Ok(handler)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    // reply with `statuses` in order, return the request bodies
+    fn mock_server(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        let handler = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    let line = line.to_ascii_lowercase();
+                    if let Some(val) = line.strip_prefix("content-length:") {
+                        length = val.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(serde_json::from_slice(&body).unwrap());
+
+                let resp = format!(
+                    "HTTP/1.1 {status} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                stream.write_all(resp.as_bytes()).unwrap();
+            }
+            bodies
+        });
+        (url, handler)
+    }
+
+    fn uploader(url: &str, batch_size: usize) -> Uploader {
+        Uploader::new(&UploaderConfig {
+            url: url.to_string(),
+            tags: None,
+            interval: None,
+            batch_size: Some(batch_size),
+            timeout: Some(1000),
+        })
+    }
+
+    #[test]
+    fn post_status() {
+        let (url, handler) = mock_server(vec![204, 500, 400, 429, 401]);
+        let uploader = uploader(&url, 10);
+        assert_eq!(uploader.post(&json!({"rows": []})).unwrap(), None);
+        assert!(uploader.post(&json!({"rows": []})).is_err());
+        assert_eq!(uploader.post(&json!({"rows": []})).unwrap(), Some(400));
+        assert!(uploader.post(&json!({"rows": []})).is_err());
+        assert!(uploader.post(&json!({"rows": []})).is_err());
+        assert_eq!(handler.join().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn rejected_status() {
+        assert!(is_rejected(400));
+        assert!(is_rejected(422));
+        assert!(!is_rejected(401));
+        assert!(!is_rejected(403));
+        assert!(!is_rejected(404));
+        assert!(!is_rejected(408));
+        assert!(!is_rejected(429));
+        assert!(!is_rejected(500));
+    }
+
+    #[test]
+    fn mark_uploaded_after_ack() {
+        let connection = sqlite::open(":memory:").unwrap();
+        connection
+            .execute(
+                "CREATE TABLE data (id INTEGER PRIMARY KEY AUTOINCREMENT, temp REAL, \
+                flag_uploaded BOOLEAN DEFAULT FALSE);
+                INSERT INTO data (temp) VALUES (1.5), (2.5), (3.5);",
+            )
+            .unwrap();
+        let shutdown = Shutdown::new();
+
+        // rejected batch is kept
+        let (url, handler) = mock_server(vec![503]);
+        let ret = uploader(&url, 2).upload_table(&connection, "CWB", "data", "db", &shutdown);
+        assert!(ret.is_err());
+        handler.join().unwrap();
+
+        // bad payload batch is skipped, the next batch is still sent
+        let (url, handler) = mock_server(vec![400, 200]);
+        let ret = uploader(&url, 2).upload_table(&connection, "CWB", "data", "db", &shutdown);
+        assert_eq!(ret.unwrap(), 1);
+        let bodies = handler.join().unwrap();
+        assert_eq!(bodies[0]["rows"].as_array().unwrap().len(), 2);
+        assert_eq!(bodies[1]["rows"][0]["temp"], json!(3.5));
+        let mut statement = connection
+            .prepare("SELECT COUNT(*) FROM data WHERE flag_uploaded = -1;")
+            .unwrap();
+        statement.next().unwrap();
+        assert_eq!(statement.read::<i64, _>(0).unwrap(), 2);
+
+        let (ids, _) = uploader(&url, 2)
+            .select_pending(&connection, "data")
+            .unwrap();
+        assert!(ids.is_empty());
+    }
+}
@@ -1,8 +1,9 @@
 use std::{
     collections::HashMap,
     error::Error,
-    fs::rename,
+    fs::{read_dir, rename},
     hash::{DefaultHasher, Hash, Hasher},
+    io,
     path::{Path, PathBuf},
 };
 
@@ -100,6 +101,30 @@ where
     Ok(())
 }
 
+// files with `suffix` under `root`, recursively
+pub fn collect_files<P>(root: P, suffix: &str, result: &mut Vec<PathBuf>) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    for entry in read_dir(root)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, suffix, result)?;
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some(suffix) {
+            result.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 pub fn generate_db_filepath(
     tag: &str,
     db_config: &CodecConfigDB,
@@ -151,3 +176,17 @@ pub fn generate_db_filepath(
     }
     Some(filepath)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_csv() {
+        assert_eq!(csv_escape("21.5"), "21.5");
+        assert_eq!(csv_escape(""), "");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("line\nnext"), "\"line\nnext\"");
+    }
+}
@@ -1 +1,4 @@
+pub mod backoff;
 pub mod files;
+pub mod shutdown;
+pub mod watcher;
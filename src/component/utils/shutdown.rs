@@ -0,0 +1,63 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const SLEEP_STEP: Duration = Duration::from_millis(100);
+
+// stop flag shared by all components, set by SIGINT/SIGTERM
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    flag: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    // return true if it was already triggered
+    pub fn trigger(&self) -> bool {
+        self.flag.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    // sleep in small steps, return false if interrupted by shutdown
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_triggered() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(SLEEP_STEP.min(deadline - now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interrupt_sleep() {
+        let shutdown = Shutdown::new();
+        assert!(shutdown.sleep(Duration::from_millis(10)));
+
+        let other = shutdown.clone();
+        let handle = thread::spawn(move || other.sleep(Duration::from_secs(60)));
+        assert!(!shutdown.trigger());
+        assert!(!handle.join().unwrap());
+        assert!(shutdown.trigger());
+    }
+}
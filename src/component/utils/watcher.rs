@@ -0,0 +1,202 @@
+use std::{
+    io::{self, ErrorKind},
+    os::fd::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use inotify::{Inotify, WatchMask};
+
+use super::shutdown::Shutdown;
+
+// longest block on the inotify fd before the shutdown flag is checked
+const WAIT_STEP: Duration = Duration::from_millis(500);
+const BUFFER_SIZE: usize = 4096;
+
+// true if `fd` is readable within `timeout`
+fn poll_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: a single pollfd which lives across the call
+    let ret = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+    if ret < 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() == ErrorKind::Interrupted {
+            return Ok(false);
+        }
+        return Err(e);
+    }
+    Ok(ret > 0)
+}
+
+// wake up on inotify events of the watched directories, blocked on the inotify fd
+// `interval` polling is kept, events are not reported on network filesystems
+pub struct DirWatcher {
+    inotify: Option<Inotify>,
+    buffer: Vec<u8>,
+}
+
+impl DirWatcher {
+    // events: file is closed after writing, moved in or created
+    pub fn new(dirs: &[PathBuf]) -> Self {
+        let mut result = DirWatcher {
+            inotify: None,
+            buffer: vec![0; BUFFER_SIZE],
+        };
+        if dirs.is_empty() {
+            return result;
+        }
+        let inotify = match Inotify::init() {
+            Ok(inotify) => inotify,
+            Err(e) => {
+                log::warn!("Inotify unavailable: {e}, fallback to polling");
+                return result;
+            }
+        };
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+        let mut watched = 0;
+        for dir in dirs {
+            match inotify.watches().add(dir, mask) {
+                Ok(_) => watched += 1,
+                Err(e) => log::warn!("Watch {dir:?} failed: {e}, fallback to polling"),
+            }
+        }
+        if watched > 0 {
+            result.inotify = Some(inotify);
+        }
+        result
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    // true if any event is pending, the events are consumed
+    fn has_events(&mut self) -> bool {
+        let Some(inotify) = self.inotify.as_mut() else {
+            return false;
+        };
+        let mut found = false;
+        loop {
+            match inotify.read_events(&mut self.buffer) {
+                Ok(mut events) => found |= events.next().is_some(),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::error!("Read inotify events failed: {e}, fallback to polling");
+                    self.inotify = None;
+                    break;
+                }
+            }
+        }
+        found
+    }
+
+    // wait for an event or `interval`, return false if interrupted by shutdown
+    pub fn wait(&mut self, interval: Duration, shutdown: &Shutdown) -> bool {
+        let deadline = Instant::now() + interval;
+        loop {
+            if shutdown.is_triggered() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            let Some(inotify) = self.inotify.as_ref() else {
+                return shutdown.sleep(deadline - now);
+            };
+            match poll_readable(inotify.as_raw_fd(), WAIT_STEP.min(deadline - now)) {
+                Ok(true) if self.has_events() => return true,
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("Poll inotify failed: {e}, fallback to polling");
+                    self.inotify = None;
+                }
+            }
+        }
+    }
+}
+
+pub fn is_glob_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// files matched by `path`, it may be a glob pattern
+pub fn expand_path(path: &str) -> Vec<PathBuf> {
+    if !is_glob_pattern(path) {
+        let path = PathBuf::from(path);
+        return if path.is_file() {
+            vec![path]
+        } else {
+            Vec::new()
+        };
+    }
+    let paths = match glob::glob(path) {
+        Ok(paths) => paths,
+        Err(e) => {
+            log::error!("Invalid listen pattern: {e} - {path:?}");
+            return Vec::new();
+        }
+    };
+    let mut result = paths
+        .filter_map(|val| val.ok())
+        .filter(|val| val.is_file())
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+// directory to watch for `path`, None if the directory itself is a pattern
+pub fn watch_dir(path: &str) -> Option<PathBuf> {
+    let parent = Path::new(path).parent()?;
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    if is_glob_pattern(&parent.to_string_lossy()) {
+        return None;
+    }
+    Some(parent.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::*;
+
+    #[test]
+    fn watch_events() {
+        let root = std::env::temp_dir().join("naho_watch_test");
+        let _ = remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        write(root.join("b.dat"), "").unwrap();
+        write(root.join("a.dat"), "").unwrap();
+        write(root.join("a.txt"), "").unwrap();
+
+        let pattern = root.join("*.dat").to_string_lossy().to_string();
+        assert_eq!(
+            expand_path(&pattern),
+            vec![root.join("a.dat"), root.join("b.dat")]
+        );
+        assert_eq!(watch_dir(&pattern), Some(root.clone()));
+        assert_eq!(watch_dir("/data/*/a.dat"), None);
+
+        let shutdown = Shutdown::new();
+        let mut watcher = DirWatcher::new(std::slice::from_ref(&root));
+        assert!(watcher.is_watching());
+        assert!(!watcher.has_events());
+        write(root.join("c.dat"), "1\n").unwrap();
+        let start = Instant::now();
+        assert!(watcher.wait(Duration::from_secs(10), &shutdown));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        shutdown.trigger();
+        assert!(!watcher.wait(Duration::from_secs(10), &shutdown));
+        remove_dir_all(root).unwrap();
+    }
+}
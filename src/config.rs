@@ -1,19 +1,218 @@
-use std::{collections::HashMap, error::Error, fs::File, io::BufReader};
+use std::{collections::HashMap, error::Error, fs::File, io::BufReader, time::Duration};
 
 use chrono::NaiveDateTime;
 use regex::Regex;
+use rumqttc::QoS;
 use serde::Deserialize;
+use serialport::{DataBits, FlowControl, Parity, SerialPortBuilder, StopBits};
 
 use crate::component::{
     codec::{CodecConfigBase, CodecConfigMetadata},
+    frame_cwb::ChecksumType,
+    parser_sitest::SENSORS,
+    queue::OverflowPolicy,
+    utils::watcher::is_glob_pattern,
     DTAETIME_FMT,
 };
 
 #[allow(unused)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct SerialPortConfig {
+    pub name: Option<String>,
+    pub tag: Option<String>,
+    pub framing: Option<String>,
     pub path: String,
     pub baudrate: u32,
+    pub data_bits: Option<u8>,
+    pub parity: Option<String>,
+    pub stop_bits: Option<u8>,
+    pub flow_control: Option<String>,
+    pub timeout: Option<u64>, // ms
+    pub dtr: Option<bool>,
+    pub rts: Option<bool>,
+    pub checksum: Option<ChecksumType>,
+}
+
+impl SerialPortConfig {
+    pub fn get_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.path)
+    }
+
+    pub fn get_tag(&self) -> &str {
+        self.tag.as_deref().unwrap_or("CWB")
+    }
+
+    pub fn builder(&self) -> Result<SerialPortBuilder, Box<dyn Error + 'static>> {
+        let data_bits = match self.data_bits {
+            None | Some(8) => DataBits::Eight,
+            Some(7) => DataBits::Seven,
+            Some(6) => DataBits::Six,
+            Some(5) => DataBits::Five,
+            Some(val) => return Err(format!("Unsupport data_bits: {val}").into()),
+        };
+
+        let parity = match self.parity.as_deref() {
+            None | Some("none") => Parity::None,
+            Some("odd") => Parity::Odd,
+            Some("even") => Parity::Even,
+            Some(val) => return Err(format!("Unsupport parity: {val:?}").into()),
+        };
+
+        let stop_bits = match self.stop_bits {
+            None | Some(1) => StopBits::One,
+            Some(2) => StopBits::Two,
+            Some(val) => return Err(format!("Unsupport stop_bits: {val}").into()),
+        };
+
+        let flow_control = match self.flow_control.as_deref() {
+            None | Some("none") => FlowControl::None,
+            Some("software") => FlowControl::Software,
+            Some("hardware") => FlowControl::Hardware,
+            Some(val) => return Err(format!("Unsupport flow_control: {val:?}").into()),
+        };
+
+        let timeout = Duration::from_millis(self.timeout.unwrap_or(100));
+
+        Ok(serialport::new(&self.path, self.baudrate)
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .flow_control(flow_control)
+            .timeout(timeout))
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct NetworkConfig {
+    pub name: String,
+    pub tag: Option<String>,
+    pub mode: String,
+    pub address: String,
+    pub timeout: Option<u64>, // ms
+    pub checksum: Option<ChecksumType>,
+}
+
+impl NetworkConfig {
+    pub fn get_tag(&self) -> &str {
+        self.tag.as_deref().unwrap_or("CWB")
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.unwrap_or(1000))
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct SinkConfig {
+    pub name: Option<String>,
+    pub kind: String,
+    pub capacity: Option<usize>,
+    pub overflow: Option<OverflowPolicy>,
+    pub spool_directory: Option<String>,
+    pub block_timeout: Option<u64>,
+    pub options: Option<serde_json::Value>,
+}
+
+impl SinkConfig {
+    pub fn new(kind: &str) -> Self {
+        SinkConfig {
+            name: None,
+            kind: kind.to_string(),
+            capacity: None,
+            overflow: None,
+            spool_directory: None,
+            block_timeout: None,
+            options: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.kind)
+    }
+
+    // wait of a full `block` queue before the sink is taken as stalled
+    pub fn get_block_timeout(&self) -> Duration {
+        Duration::from_millis(self.block_timeout.unwrap_or(10000))
+    }
+}
+
+// `options` of sink kind `mqtt`
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic: Option<String>,
+    pub station: Option<String>,
+    pub station_field: Option<String>,
+    pub qos: Option<u8>,
+    pub retain: Option<bool>,
+    pub keep_alive: Option<u64>, // s
+    pub offline_capacity: Option<usize>,
+}
+
+impl MqttConfig {
+    pub fn from_sink(cfg: &SinkConfig) -> Result<Self, Box<dyn Error + 'static>> {
+        let Some(options) = cfg.options.clone() else {
+            return Err(String::from("options is required").into());
+        };
+        Ok(serde_json::from_value(options)?)
+    }
+
+    pub fn get_topic(&self) -> &str {
+        self.topic
+            .as_deref()
+            .unwrap_or("naho/{tag}/{dkind}/{station}")
+    }
+
+    pub fn get_qos(&self) -> Result<QoS, Box<dyn Error + 'static>> {
+        match self.qos {
+            Some(0) => Ok(QoS::AtMostOnce),
+            None | Some(1) => Ok(QoS::AtLeastOnce),
+            Some(2) => Ok(QoS::ExactlyOnce),
+            Some(val) => Err(format!("Unsupport qos: {val}").into()),
+        }
+    }
+
+    pub fn get_keep_alive(&self) -> Duration {
+        Duration::from_secs(self.keep_alive.unwrap_or(30))
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct UploaderConfig {
+    pub url: String,
+    pub tags: Option<Vec<String>>,
+    pub interval: Option<u64>, // s
+    pub batch_size: Option<usize>,
+    pub timeout: Option<u64>, // ms
+}
+
+impl UploaderConfig {
+    // default: all tags with sqlite3 recorder
+    pub fn is_enabled_tag(&self, tag: &str) -> bool {
+        self.tags
+            .as_ref()
+            .is_none_or(|tags| tags.iter().any(|val| val == tag))
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(60))
+    }
+
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size.unwrap_or(500)
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.unwrap_or(10000))
+    }
 }
 
 #[allow(unused)]
@@ -35,19 +234,79 @@ pub struct ListenConfig {
     pub name: String,
     pub path: String,
     pub ftype: String,
+    pub pattern: Option<String>,
     pub tag: String,
     pub dkind: String,
     pub header: Option<ListenConfigHeader>,
     pub flags: Option<ListenConfigFlags>,
 }
 
+impl ListenConfig {
+    // files of a `dir` target, default: all files
+    pub fn get_pattern(&self) -> &str {
+        self.pattern.as_deref().unwrap_or("*")
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Deserialize)]
 pub struct GlobalConfig {
     pub log4rs_cfg: String,
-    pub serial_port: SerialPortConfig,
+    pub serial_port: Option<SerialPortConfig>,
+    pub serial_list: Option<Vec<SerialPortConfig>>,
+    pub network_list: Option<Vec<NetworkConfig>>,
     pub listen_move_suffix: Option<String>,
     pub listen_list: Option<Vec<ListenConfig>>,
+    pub listen_state: Option<String>,
+    pub listen_interval: Option<u64>,
+    pub listen_watch: Option<bool>,
+    pub channel_capacity: Option<usize>,
+    pub sink_list: Option<Vec<SinkConfig>>,
+    pub uploader: Option<UploaderConfig>,
+}
+
+impl GlobalConfig {
+    // `serial_port` and `serial_list` are merged
+    pub fn get_serial_list(&self) -> Vec<&SerialPortConfig> {
+        let mut result = Vec::new();
+        if let Some(cfg) = self.serial_port.as_ref() {
+            result.push(cfg);
+        }
+        if let Some(list) = self.serial_list.as_ref() {
+            result.extend(list.iter());
+        }
+        result
+    }
+
+    // read positions of the followed listen files
+    pub fn get_listen_state(&self) -> &str {
+        self.listen_state
+            .as_deref()
+            .unwrap_or("data/listen_state.json")
+    }
+
+    // polling interval of the listen files, events of inotify wake up earlier
+    pub fn get_listen_interval(&self) -> Duration {
+        Duration::from_secs(self.listen_interval.unwrap_or(5))
+    }
+
+    // default: watch the listen directories by inotify
+    pub fn get_listen_watch(&self) -> bool {
+        self.listen_watch.unwrap_or(true)
+    }
+
+    // messages between readers and the dispatcher, a full channel blocks the readers
+    pub fn get_channel_capacity(&self) -> usize {
+        self.channel_capacity.unwrap_or(1000)
+    }
+
+    // default: rawdata and sqlite3 recorders
+    pub fn get_sink_list(&self) -> Vec<SinkConfig> {
+        match self.sink_list.as_ref() {
+            Some(list) => list.clone(),
+            None => vec![SinkConfig::new("rawdata"), SinkConfig::new("sqlite3")],
+        }
+    }
 }
 
 #[allow(unused)]
@@ -65,6 +324,262 @@ impl SystemConfig {
 
         Ok(config)
     }
+
+    // `codec.rawdata_header`, default: true if a listen target of the tag has a header
+    pub fn get_rawdata_header(&self, tag: &str) -> bool {
+        if let Some(val) = self.codec.get(tag).and_then(|cfg| cfg.rawdata_header) {
+            return val;
+        }
+        self.global
+            .listen_list
+            .iter()
+            .flatten()
+            .any(|val| val.tag == tag && val.header.is_some())
+    }
+
+    // return a list of problems, empty if config is usable
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Err(e) =
+            log4rs::config::load_config_file(&self.global.log4rs_cfg, Default::default())
+        {
+            problems.push(format!("global.log4rs_cfg: {e}"));
+        }
+
+        let serial_list = self.global.get_serial_list();
+        for (idx, val) in serial_list.iter().enumerate() {
+            let name = val.get_name();
+            if let Err(e) = val.builder() {
+                problems.push(format!("global.serial_list.{name}: {e}"));
+            }
+            if !self.codec.contains_key(val.get_tag()) {
+                problems.push(format!(
+                    "global.serial_list.{name}: codec not found for tag {:?}",
+                    val.get_tag()
+                ));
+            }
+            if !matches!(val.framing.as_deref(), None | Some("stream") | Some("line")) {
+                problems.push(format!(
+                    "global.serial_list.{name}: unsupport framing {:?}",
+                    val.framing
+                ));
+            }
+            if serial_list[..idx]
+                .iter()
+                .any(|other| other.get_name() == name)
+            {
+                problems.push(format!("global.serial_list.{name}: duplicated name"));
+            }
+            if serial_list[..idx]
+                .iter()
+                .any(|other| other.path == val.path)
+            {
+                problems.push(format!(
+                    "global.serial_list.{name}.path: duplicated path {:?}",
+                    val.path
+                ));
+            }
+        }
+
+        if let Some(network_list) = self.global.network_list.as_ref() {
+            for val in network_list {
+                if !matches!(val.mode.as_str(), "tcp_client" | "tcp_server" | "udp") {
+                    problems.push(format!(
+                        "global.network_list.{}: unsupport mode {:?}",
+                        val.name, val.mode
+                    ));
+                }
+                if !self.codec.contains_key(val.get_tag()) {
+                    problems.push(format!(
+                        "global.network_list.{}: codec not found for tag {:?}",
+                        val.name,
+                        val.get_tag()
+                    ));
+                }
+            }
+        }
+
+        let sink_list = self.global.get_sink_list();
+        for (idx, val) in sink_list.iter().enumerate() {
+            let name = val.get_name();
+            if sink_list[..idx]
+                .iter()
+                .any(|other| other.get_name() == name)
+            {
+                problems.push(format!("global.sink_list.{name}: duplicated name"));
+            }
+            if val.capacity == Some(0) {
+                problems.push(format!(
+                    "global.sink_list.{name}: capacity must be positive"
+                ));
+            }
+            if val.kind == "mqtt" {
+                if let Err(e) = MqttConfig::from_sink(val).and_then(|cfg| cfg.get_qos()) {
+                    problems.push(format!("global.sink_list.{name}.options: {e}"));
+                }
+            }
+        }
+
+        if let Some(uploader) = self.global.uploader.as_ref() {
+            if !uploader.url.starts_with("http://") && !uploader.url.starts_with("https://") {
+                problems.push(format!("global.uploader.url: unsupport {:?}", uploader.url));
+            }
+            if uploader.batch_size == Some(0) {
+                problems.push(String::from("global.uploader.batch_size: must be positive"));
+            }
+            for tag in uploader.tags.iter().flatten() {
+                if self.codec.get(tag).is_none_or(|val| val.sqlite3.is_none()) {
+                    problems.push(format!(
+                        "global.uploader.tags: sqlite3 not found for {tag:?}"
+                    ));
+                }
+            }
+        }
+
+        if let Some(listen_list) = self.global.listen_list.as_ref() {
+            for val in listen_list {
+                let Some(codec) = self.codec.get(&val.tag) else {
+                    problems.push(format!(
+                        "global.listen_list.{}: codec not found for tag {:?}",
+                        val.name, val.tag
+                    ));
+                    continue;
+                };
+                if let Err(e) = glob::Pattern::new(&val.path) {
+                    problems.push(format!("global.listen_list.{}.path: {e}", val.name));
+                }
+                if let Err(e) = glob::Pattern::new(val.get_pattern()) {
+                    problems.push(format!("global.listen_list.{}.pattern: {e}", val.name));
+                }
+                match val.ftype.as_str() {
+                    "file" | "dir" => {}
+                    "pipe" if is_glob_pattern(&val.path) => problems.push(format!(
+                        "global.listen_list.{}.path: pipe path can't be a pattern",
+                        val.name
+                    )),
+                    "pipe" => {}
+                    ftype => problems.push(format!(
+                        "global.listen_list.{}.ftype: Unsupport ftype {ftype:?}",
+                        val.name
+                    )),
+                }
+                if codec.get_data_config(&val.dkind).is_none() {
+                    problems.push(format!(
+                        "global.listen_list.{}: dkind {:?} not found in codec {:?}",
+                        val.name, val.dkind, val.tag
+                    ));
+                }
+            }
+        }
+
+        for (key, val) in self.codec.iter() {
+            if key != &val.tag {
+                problems.push(format!("codec.{key}: tag {:?} not match key", val.tag));
+            }
+
+            for (db_type, db) in [
+                ("rawdata", &val.rawdata),
+                ("l1_data", &val.l1_data),
+                ("sqlite3", &val.sqlite3),
+            ] {
+                let Some(regexp) = db.as_ref().and_then(|db| db.regex.as_ref()) else {
+                    continue;
+                };
+                if let Err(e) = Regex::new(regexp) {
+                    problems.push(format!("codec.{key}.{db_type}.regex: {e}"));
+                }
+            }
+
+            for dconfig in val.metadatas.iter() {
+                if dconfig.get_datetime_info().is_none() {
+                    problems.push(format!(
+                        "codec.{key}.metadatas.{}: major_datetime not found",
+                        dconfig.name
+                    ));
+                }
+
+                for dtype in dconfig.formation.iter() {
+                    let Some(qc) = dtype.qc.as_ref() else {
+                        continue;
+                    };
+                    let name = &dtype.sqlite3.name;
+                    if let (Some(min), Some(max)) = (qc.min, qc.max) {
+                        if min > max {
+                            problems.push(format!(
+                                "codec.{key}.metadatas.{}.{name}.qc: min is larger than max",
+                                dconfig.name
+                            ));
+                        }
+                    }
+                    if qc.persistence.is_some_and(|val| val < 2) {
+                        problems.push(format!(
+                            "codec.{key}.metadatas.{}.{name}.qc.persistence: should be at least 2",
+                            dconfig.name
+                        ));
+                    }
+                    if let Some(upper) = qc.upper.as_deref() {
+                        if !dconfig
+                            .formation
+                            .iter()
+                            .any(|val| val.sqlite3.name == upper)
+                        {
+                            problems.push(format!(
+                                "codec.{key}.metadatas.{}.{name}.qc.upper: {upper:?} not found",
+                                dconfig.name
+                            ));
+                        }
+                    }
+                }
+
+                let layouts = dconfig.layouts.as_deref().unwrap_or_default();
+                for layout in layouts.iter() {
+                    for name in layout.omit.iter() {
+                        if !dconfig.formation.iter().any(|val| &val.rust.name == name) {
+                            problems.push(format!(
+                                "codec.{key}.metadatas.{}.layouts.{}: field {name:?} not found",
+                                dconfig.name, layout.name
+                            ));
+                        }
+                    }
+                }
+                if let Some(cfg) = dconfig.sitest.as_ref() {
+                    if !dconfig
+                        .formation
+                        .iter()
+                        .any(|val| val.rust.name == cfg.get_field())
+                    {
+                        problems.push(format!(
+                            "codec.{key}.metadatas.{}.sitest.field: {:?} not found",
+                            dconfig.name,
+                            cfg.get_field()
+                        ));
+                    }
+                    for sensor in cfg.get_sensors() {
+                        if !SENSORS.contains(&sensor.as_str()) {
+                            problems.push(format!(
+                                "codec.{key}.metadatas.{}.sitest.sensors: \
+                                unsupport sensor {sensor:?}",
+                                dconfig.name
+                            ));
+                        }
+                    }
+                }
+                if let Some(name) = dconfig.layout.as_deref() {
+                    if name != CodecConfigMetadata::DEFAULT_LAYOUT
+                        && !layouts.iter().any(|val| val.name == name)
+                    {
+                        problems.push(format!(
+                            "codec.{key}.metadatas.{}.layout: {name:?} not found",
+                            dconfig.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        problems
+    }
 }
 
 pub fn placeholder_get_tag<'a>(placeholder: &'a str) -> Option<&'a str> {
@@ -184,4 +699,122 @@ mod test {
         }
         println!("{:?}", pattern);
     }
+
+    #[test]
+    fn serial_builder() {
+        let cfg: SerialPortConfig = serde_json::from_value(serde_json::json!({
+            "path": "/dev/ttyUSB0",
+            "baudrate": 9600,
+            "data_bits": 7,
+            "parity": "even",
+            "stop_bits": 1,
+            "flow_control": "hardware",
+        }))
+        .unwrap();
+        let expected = serialport::new("/dev/ttyUSB0", 9600)
+            .data_bits(DataBits::Seven)
+            .parity(Parity::Even)
+            .stop_bits(StopBits::One)
+            .flow_control(FlowControl::Hardware)
+            .timeout(Duration::from_millis(100));
+        assert_eq!(cfg.builder().unwrap(), expected);
+
+        let mut invalid = cfg.clone();
+        invalid.parity = Some(String::from("mark"));
+        let e = invalid.builder().unwrap_err();
+        assert_eq!(e.to_string(), "Unsupport parity: \"mark\"");
+
+        let mut invalid = cfg.clone();
+        invalid.data_bits = Some(9);
+        let e = invalid.builder().unwrap_err();
+        assert_eq!(e.to_string(), "Unsupport data_bits: 9");
+    }
+
+    #[test]
+    fn rawdata_header_default() {
+        let listen = |tag: &str| {
+            serde_json::json!({
+                "name": tag,
+                "path": "/data/*.dat",
+                "ftype": "file",
+                "tag": tag,
+                "dkind": "Min",
+                "header": {"number": 4},
+            })
+        };
+        let config: SystemConfig = serde_json::from_value(serde_json::json!({
+            "global": {
+                "log4rs_cfg": "./config/log4rs.yml",
+                "listen_list": [listen("NAHO"), listen("VAISALA")],
+            },
+            "codec": {
+                "NAHO": {"tag": "NAHO", "metadatas": []},
+                "VAISALA": {"tag": "VAISALA", "rawdata_header": false, "metadatas": []},
+                "CWB": {"tag": "CWB", "metadatas": []},
+            },
+        }))
+        .unwrap();
+        assert!(config.get_rawdata_header("NAHO"));
+        assert!(!config.get_rawdata_header("VAISALA"));
+        assert!(!config.get_rawdata_header("CWB"));
+    }
+
+    fn serial_config(serial_port: Value, serial_list: Value) -> SystemConfig {
+        let config = serde_json::json!({
+            "global": {
+                "log4rs_cfg": "./config/log4rs.yml",
+                "serial_port": serial_port,
+                "serial_list": serial_list,
+            },
+            "codec": {},
+        });
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn merge_serial_list() {
+        let config = serial_config(
+            serde_json::json!({"path": "/dev/ttyUSB0", "baudrate": 9600}),
+            serde_json::json!([
+                {"name": "rain", "path": "/dev/ttyUSB1", "baudrate": 9600},
+                {"name": "wind", "path": "/dev/ttyUSB2", "baudrate": 19200},
+            ]),
+        );
+        let names = config
+            .global
+            .get_serial_list()
+            .iter()
+            .map(|val| val.get_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["/dev/ttyUSB0", "rain", "wind"]);
+
+        let config = serial_config(
+            Value::Null,
+            serde_json::json!([{"path": "/dev/ttyUSB1", "baudrate": 9600}]),
+        );
+        assert_eq!(config.global.get_serial_list().len(), 1);
+    }
+
+    #[test]
+    fn check_duplicated_serial() {
+        let config = serial_config(
+            serde_json::json!({"name": "rain", "path": "/dev/ttyUSB0", "baudrate": 9600}),
+            serde_json::json!([
+                {"name": "rain", "path": "/dev/ttyUSB1", "baudrate": 9600},
+                {"name": "wind", "path": "/dev/ttyUSB0", "baudrate": 9600},
+            ]),
+        );
+        let problems = config
+            .check()
+            .into_iter()
+            .filter(|val| val.contains("duplicated"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            vec![
+                "global.serial_list.rain: duplicated name",
+                "global.serial_list.wind.path: duplicated path \"/dev/ttyUSB0\"",
+            ]
+        );
+    }
 }